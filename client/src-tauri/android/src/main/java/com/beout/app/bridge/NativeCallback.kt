@@ -0,0 +1,11 @@
+package com.beout.app.bridge
+
+/**
+ * Answers a call Rust is waiting on. The id comes from the Rust side; exactly
+ * one of [result] and [error] should be non-null. Registered by the native
+ * bridge before it hands out any id.
+ */
+object NativeCallback {
+    @JvmStatic
+    external fun complete(id: Long, result: String?, error: String?)
+}
@@ -8,6 +8,7 @@ import androidx.credentials.GetCredentialRequest
 import androidx.credentials.GetCredentialResponse
 import androidx.credentials.CustomCredential
 import androidx.credentials.exceptions.GetCredentialException
+import com.beout.app.bridge.NativeCallback
 import com.google.android.libraries.identity.googleid.GetGoogleIdOption
 import com.google.android.libraries.identity.googleid.GoogleIdTokenCredential
 import com.google.android.libraries.identity.googleid.GoogleIdTokenParsingException
@@ -15,7 +16,6 @@ import kotlinx.coroutines.CoroutineScope
 import kotlinx.coroutines.Dispatchers
 import kotlinx.coroutines.launch
 import kotlinx.coroutines.withContext
-import kotlinx.coroutines.CompletableDeferred
 import org.json.JSONObject
 
 class GoogleSignInManager {
@@ -89,106 +89,21 @@ class GoogleSignInManager {
     }
 
     /**
-     * Synchronous wrapper for JNI calls from Rust
-     * This method blocks until the sign-in is complete
+     * Entry point for Rust: runs [signIn] and reports the result JSON through
+     * [NativeCallback] with the id Rust handed in.
      */
-    fun signInSync(
-        filterByAuthorizedAccounts: Boolean = true,
-        autoSelectEnabled: Boolean = true,
-        nonce: String? = null
-    ): String {
-        val activity = currentActivity
-        if (activity == null) {
-            return JSONObject().apply {
-                put("success", false)
-                put("error", "Activity not available")
-            }.toString()
-        }
-
-        return try {
-            // Use a CompletableDeferred to make this synchronous
-            val deferred = kotlinx.coroutines.CompletableDeferred<String>()
-
-            CoroutineScope(Dispatchers.Main).launch {
-                try {
-                    val googleIdOption = GetGoogleIdOption.Builder()
-                        .setFilterByAuthorizedAccounts(filterByAuthorizedAccounts)
-                        .setServerClientId(WEB_CLIENT_ID)
-                        .setAutoSelectEnabled(autoSelectEnabled)
-                        .apply {
-                            nonce?.let { setNonce(it) }
-                        }
-                        .build()
-
-                    val request = GetCredentialRequest.Builder()
-                        .addCredentialOption(googleIdOption)
-                        .build()
-
-                    Log.d(TAG, "Starting synchronous credential request")
-
-                    val result = credentialManager.getCredential(
-                        request = request,
-                        context = activity
-                    )
-
-                    val resultJson = handleSignInResultSync(result)
-                    deferred.complete(resultJson)
-
-                } catch (e: GetCredentialException) {
-                    Log.e(TAG, "Synchronous sign-in failed", e)
-
-                    // If no authorized accounts found and we were filtering, try again without filtering
-                    if (filterByAuthorizedAccounts && e.message?.contains("No credentials available") == true) {
-                        Log.d(TAG, "No authorized accounts found, trying sign-up flow")
-                        try {
-                            val googleIdOption = GetGoogleIdOption.Builder()
-                                .setFilterByAuthorizedAccounts(false)
-                                .setServerClientId(WEB_CLIENT_ID)
-                                .setAutoSelectEnabled(autoSelectEnabled)
-                                .apply {
-                                    nonce?.let { setNonce(it) }
-                                }
-                                .build()
-
-                            val request = GetCredentialRequest.Builder()
-                                .addCredentialOption(googleIdOption)
-                                .build()
-
-                            val result = credentialManager.getCredential(
-                                request = request,
-                                context = activity
-                            )
-
-                            val resultJson = handleSignInResultSync(result)
-                            deferred.complete(resultJson)
-                        } catch (retryException: Exception) {
-                            val errorJson = JSONObject().apply {
-                                put("success", false)
-                                put("error", retryException.message ?: "Unknown error during sign-in retry")
-                            }.toString()
-                            deferred.complete(errorJson)
-                        }
-                    } else {
-                        val errorJson = JSONObject().apply {
-                            put("success", false)
-                            put("error", e.message ?: "Unknown error during sign-in")
-                        }.toString()
-                        deferred.complete(errorJson)
-                    }
-                }
-            }
-
-            // Block until the coroutine completes
-            kotlinx.coroutines.runBlocking {
-                deferred.await()
+    fun signInAsync(
+        filterByAuthorizedAccounts: Boolean,
+        autoSelectEnabled: Boolean,
+        nonce: String?,
+        callbackId: Long
+    ) {
+        signIn(filterByAuthorizedAccounts, autoSelectEnabled, nonce) { success, result, error ->
+            if (success) {
+                NativeCallback.complete(callbackId, result, null)
+            } else {
+                NativeCallback.complete(callbackId, null, error ?: "Unknown error during sign-in")
             }
-
-        } catch (e: Exception) {
-            Log.e(TAG, "Synchronous sign-in wrapper failed", e)
-            JSONObject().apply {
-                put("success", false)
-                put("error", e.message ?: "Unknown error in synchronous wrapper")
-            }.toString()
         }
     }
 
@@ -235,52 +150,6 @@ class GoogleSignInManager {
         }
     }
 
-    private fun handleSignInResultSync(result: GetCredentialResponse): String {
-        val credential = result.credential
-
-        return when (credential) {
-            is CustomCredential -> {
-                if (credential.type == GoogleIdTokenCredential.TYPE_GOOGLE_ID_TOKEN_CREDENTIAL) {
-                    try {
-                        val googleIdTokenCredential = GoogleIdTokenCredential.createFrom(credential.data)
-
-                        // Create result JSON with all the information we need
-                        JSONObject().apply {
-                            put("success", true)
-                            put("idToken", googleIdTokenCredential.idToken)
-                            put("displayName", googleIdTokenCredential.displayName ?: "")
-                            put("familyName", googleIdTokenCredential.familyName ?: "")
-                            put("givenName", googleIdTokenCredential.givenName ?: "")
-                            put("id", googleIdTokenCredential.id)
-                            put("phoneNumber", googleIdTokenCredential.phoneNumber ?: "")
-                            put("profilePictureUri", googleIdTokenCredential.profilePictureUri?.toString() ?: "")
-                        }.toString()
-
-                    } catch (e: GoogleIdTokenParsingException) {
-                        Log.e(TAG, "Invalid Google ID token response", e)
-                        JSONObject().apply {
-                            put("success", false)
-                            put("error", "Invalid Google ID token response: ${e.message}")
-                        }.toString()
-                    }
-                } else {
-                    Log.e(TAG, "Unexpected credential type: ${credential.type}")
-                    JSONObject().apply {
-                        put("success", false)
-                        put("error", "Unexpected credential type")
-                    }.toString()
-                }
-            }
-            else -> {
-                Log.e(TAG, "Unexpected credential type: ${credential::class.java.simpleName}")
-                JSONObject().apply {
-                    put("success", false)
-                    put("error", "Unexpected credential type")
-                }.toString()
-            }
-        }
-    }
-
     fun signOut(callback: (success: Boolean, error: String?) -> Unit) {
         val activity = currentActivity
         if (activity == null) {
@@ -301,4 +170,11 @@ class GoogleSignInManager {
             }
         }
     }
+
+    /** Entry point for Rust: runs [signOut] and reports through [NativeCallback]. */
+    fun signOutAsync(callbackId: Long) {
+        signOut { success, error ->
+            NativeCallback.complete(callbackId, null, if (success) null else error ?: "Unknown error during sign-out")
+        }
+    }
 }
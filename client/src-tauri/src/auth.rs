@@ -0,0 +1,141 @@
+//! Google sign-in through `tauri-plugin-google-auth`.
+//!
+//! The plugin is registered from `setup` instead of on the builder: a plugin
+//! whose setup fails on the builder aborts startup, while a failed runtime
+//! registration only leaves sign-in unavailable. That covers a native class
+//! missing from the Android build and a rejected `plugins.google-auth` config.
+//! A panic during setup is not caught, as it poisons Tauri's plugin store.
+//!
+//! On desktop the plugin is not registered at all without a `desktopClientId`:
+//! Google refuses the loopback redirect for the web client it would fall back
+//! to, so sign-in could only fail once the browser is already open.
+//!
+//! `tauri.conf.json` leaves `desktopClientId` out on purpose: Be Out has no
+//! "Desktop app" OAuth client yet, so desktop builds start with sign-in
+//! reported as degraded. Adding the client ID under `plugins.google-auth` is
+//! all it takes to turn it on.
+
+use std::sync::{Arc, RwLock};
+
+use async_trait::async_trait;
+use serde::Serialize;
+use tauri::{command, AppHandle, Manager, Runtime, State};
+use tauri_plugin_google_auth::GoogleAuthExt;
+
+use crate::session::{CredentialSource, Provider, ProviderCredential, SessionManager};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AuthStatus {
+    /// Registration has not run yet.
+    Pending,
+    Ready,
+    /// The plugin could not or should not be registered; sign-in is unavailable.
+    Degraded,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthDiagnostics {
+    pub status: AuthStatus,
+    /// Why sign-in is unavailable, when it is.
+    pub reason: Option<String>,
+    pub platform: &'static str,
+}
+
+/// Outcome of registering the plugin, for [`auth_diagnostics`].
+pub struct AuthState(RwLock<AuthDiagnostics>);
+
+impl Default for AuthState {
+    fn default() -> Self {
+        Self(RwLock::new(AuthDiagnostics {
+            status: AuthStatus::Pending,
+            reason: None,
+            platform: std::env::consts::OS,
+        }))
+    }
+}
+
+impl AuthState {
+    fn set(&self, status: AuthStatus, reason: Option<String>) {
+        let mut diagnostics = self.0.write().unwrap();
+        diagnostics.status = status;
+        diagnostics.reason = reason;
+    }
+}
+
+/// Registers the google-auth plugin, recording a failure instead of returning
+/// it. Expects [`AuthState`] and [`SessionManager`] to be managed already.
+pub fn init<R: Runtime>(app: &AppHandle<R>) {
+    let state = app.state::<AuthState>();
+    if let Some(reason) = missing_config(app) {
+        log::warn!("Google sign-in unavailable: {reason}");
+        state.set(AuthStatus::Degraded, Some(reason));
+        return;
+    }
+    match app.plugin(tauri_plugin_google_auth::init()) {
+        Ok(()) => {
+            log::info!("Google sign-in available");
+            state.set(AuthStatus::Ready, None);
+            app.state::<SessionManager>()
+                .set_credential_source(Arc::new(GoogleCredentials(app.clone())));
+        }
+        Err(e) => {
+            log::error!("Google sign-in unavailable: {e}");
+            state.set(AuthStatus::Degraded, Some(e.to_string()));
+        }
+    }
+}
+
+/// What `plugins.google-auth` lacks for sign-in to work on this platform.
+#[cfg(desktop)]
+fn missing_config<R: Runtime>(app: &AppHandle<R>) -> Option<String> {
+    let configured = app
+        .config()
+        .plugins
+        .0
+        .get("google-auth")
+        .and_then(|config| config.get("desktopClientId"))
+        .and_then(|client_id| client_id.as_str())
+        .is_some_and(|client_id| !client_id.is_empty());
+    (!configured).then(|| {
+        "`plugins.google-auth.desktopClientId` is not set; the desktop loopback flow \
+         needs an OAuth client of the \"Desktop app\" type"
+            .into()
+    })
+}
+
+#[cfg(mobile)]
+fn missing_config<R: Runtime>(_app: &AppHandle<R>) -> Option<String> {
+    None
+}
+
+/// Renews Be Out sessions with a fresh Google ID token from the plugin.
+struct GoogleCredentials<R: Runtime>(AppHandle<R>);
+
+#[async_trait]
+impl<R: Runtime> CredentialSource for GoogleCredentials<R> {
+    async fn refresh(&self, provider: Provider) -> Option<ProviderCredential> {
+        if provider != Provider::Google {
+            return None;
+        }
+        match self.0.google_auth().tokens(true).await {
+            Ok(tokens) => Some(ProviderCredential::Google {
+                id_token: Some(tokens.id_token),
+                code: None,
+                code_verifier: None,
+                redirect_uri: None,
+                client_id: None,
+            }),
+            Err(e) => {
+                log::warn!("Failed to get a fresh Google ID token: {e}");
+                None
+            }
+        }
+    }
+}
+
+#[command]
+pub fn auth_diagnostics(state: State<'_, AuthState>) -> AuthDiagnostics {
+    state.0.read().unwrap().clone()
+}
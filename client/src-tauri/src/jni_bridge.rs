@@ -0,0 +1,465 @@
+//! Calls into Java over JNI without the usual footguns.
+//!
+//! - The `JavaVM` is resolved once and cached.
+//! - Every call runs with the current thread attached, and a thread the bridge
+//!   attached is detached again afterwards.
+//! - Local references are confined to a frame per call; anything kept is a
+//!   `GlobalRef`.
+//! - A Java exception left pending by a call is cleared and returned as
+//!   [`BridgeError::JavaException`].
+//! - Long-running Java work answers through [`PendingCall`] instead of blocking
+//!   a Rust thread.
+//!
+//! On Android the bridge is built from `ndk-context`. Elsewhere, with the `jvm`
+//! feature, a VM started through the `jni` invocation API can be
+//! [`install`](JniBridge::install)ed, so the bridge can be exercised against a
+//! desktop JVM. That is what the tests do, so they need the feature and a JDK:
+//! `JAVA_HOME=... cargo test --features jvm`.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{Mutex, OnceLock},
+    task::{Context, Poll},
+};
+
+use jni::{
+    objects::{GlobalRef, JClass, JString, JThrowable, JValue},
+    sys::jlong,
+    JNIEnv, JavaVM, NativeMethod,
+};
+use tokio::sync::oneshot;
+
+/// Class whose static native `complete(long, String, String)` Java code calls to
+/// answer a [`PendingCall`].
+pub const CALLBACK_CLASS: &str = "com/beout/app/bridge/NativeCallback";
+/// Local references a single [`JniBridge::with_env`] call may hold before the
+/// JVM has to grow the frame.
+const LOCAL_FRAME_CAPACITY: i32 = 32;
+
+static BRIDGE: OnceLock<JniBridge> = OnceLock::new();
+static CALLBACKS: OnceLock<Mutex<Callbacks>> = OnceLock::new();
+
+#[derive(Debug, thiserror::Error)]
+pub enum BridgeError {
+    #[error("no Java VM available: {0}")]
+    Unavailable(&'static str),
+    #[error("{class}: {}", message.as_deref().unwrap_or("(no message)"))]
+    JavaException {
+        /// Binary name of the exception class, e.g. `java.lang.IllegalStateException`.
+        class: String,
+        message: Option<String>,
+    },
+    #[error("the Java side reported an error: {0}")]
+    Callback(String),
+    #[error("the Java side dropped the call without answering")]
+    Dropped,
+    #[error(transparent)]
+    Jni(#[from] jni::errors::Error),
+}
+
+pub type Result<T> = std::result::Result<T, BridgeError>;
+
+pub struct JniBridge {
+    vm: JavaVM,
+    /// The Android context from `ndk-context`, which is the activity in a Tauri app.
+    context: Option<GlobalRef>,
+    /// Loader of the app's classes. Threads attached from native code only see
+    /// the system class loader through `FindClass`.
+    class_loader: Option<GlobalRef>,
+    classes: Mutex<HashMap<String, GlobalRef>>,
+    callbacks_registered: Mutex<bool>,
+}
+
+impl JniBridge {
+    /// A bridge for `vm` that finds classes through `FindClass`.
+    pub fn new(vm: JavaVM) -> Self {
+        Self {
+            vm,
+            context: None,
+            class_loader: None,
+            classes: Mutex::new(HashMap::new()),
+            callbacks_registered: Mutex::new(false),
+        }
+    }
+
+    /// Makes `bridge` the one [`get`](Self::get) returns, unless one was set
+    /// already.
+    pub fn install(bridge: Self) -> &'static Self {
+        BRIDGE.get_or_init(|| bridge)
+    }
+
+    /// The process-wide bridge, created from `ndk-context` on first use on
+    /// Android.
+    pub fn get() -> Result<&'static Self> {
+        if let Some(bridge) = BRIDGE.get() {
+            return Ok(bridge);
+        }
+        #[cfg(target_os = "android")]
+        {
+            let bridge = Self::from_android_context()?;
+            Ok(BRIDGE.get_or_init(|| bridge))
+        }
+        #[cfg(not(target_os = "android"))]
+        Err(BridgeError::Unavailable("no bridge installed"))
+    }
+
+    #[cfg(target_os = "android")]
+    fn from_android_context() -> Result<Self> {
+        let android = ndk_context::android_context();
+        // SAFETY: ndk-context hands out the process' JavaVM, which lives as long
+        // as the process.
+        let vm = unsafe { JavaVM::from_raw(android.vm().cast()) }
+            .map_err(|_| BridgeError::Unavailable("ndk-context has no JavaVM"))?;
+        let mut bridge = Self::new(vm);
+        let (context, class_loader) = bridge.with_env(|env| {
+            // SAFETY: ndk-context holds a global reference to the context for
+            // the lifetime of the app; it is only borrowed here to take our own.
+            let context = unsafe { jni::objects::JObject::from_raw(android.context().cast()) };
+            if context.is_null() {
+                return Err(BridgeError::Unavailable("ndk-context has no Android context"));
+            }
+            let loader = env
+                .call_method(&context, "getClassLoader", "()Ljava/lang/ClassLoader;", &[])?
+                .l()?;
+            Ok((env.new_global_ref(&context)?, env.new_global_ref(loader)?))
+        })?;
+        bridge.context = Some(context);
+        bridge.class_loader = Some(class_loader);
+        Ok(bridge)
+    }
+
+    pub fn vm(&self) -> &JavaVM {
+        &self.vm
+    }
+
+    /// The Android context from `ndk-context`, on Android.
+    pub fn context(&self) -> Option<&GlobalRef> {
+        self.context.as_ref()
+    }
+
+    /// Runs `f` with a `JNIEnv` for the current thread, attaching it for the
+    /// duration of the call if needed. Local references created by `f` are
+    /// released when it returns, so anything it returns that must outlive the
+    /// call has to be a `GlobalRef`.
+    ///
+    /// An exception `f` leaves pending is cleared and takes precedence over
+    /// `f`'s own result, as it explains the failure better than `jni`'s
+    /// `JavaException` error.
+    pub fn with_env<T>(&self, f: impl FnOnce(&mut JNIEnv) -> Result<T>) -> Result<T> {
+        let mut env = self.vm.attach_current_thread()?;
+        env.with_local_frame(LOCAL_FRAME_CAPACITY, |env| {
+            let result = f(env);
+            take_exception(env)?;
+            result
+        })
+    }
+
+    /// Looks up the class with the binary `name` (`com/example/Foo`) through the
+    /// app's class loader, caching it as a global reference.
+    pub fn load_class(&self, env: &mut JNIEnv, name: &str) -> Result<GlobalRef> {
+        if let Some(class) = self.classes.lock().unwrap().get(name) {
+            return Ok(class.clone());
+        }
+
+        let class = match &self.class_loader {
+            Some(loader) => {
+                let java_name = env.new_string(name.replace('/', "."))?;
+                env.call_method(
+                    loader,
+                    "loadClass",
+                    "(Ljava/lang/String;)Ljava/lang/Class;",
+                    &[JValue::Object(&java_name)],
+                )?
+                .l()?
+            }
+            None => env.find_class(name)?.into(),
+        };
+        let class = env.new_global_ref(class)?;
+        self.classes
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), class.clone());
+        Ok(class)
+    }
+
+    /// A call Java answers later by passing [`PendingCall::id`] to
+    /// `NativeCallback.complete`. Registers that native method on first use.
+    pub fn pending_call(&self) -> Result<PendingCall> {
+        self.register_callbacks()?;
+        let (sender, receiver) = oneshot::channel();
+        let mut callbacks = callbacks();
+        callbacks.next_id += 1;
+        let id = callbacks.next_id;
+        callbacks.pending.insert(id, sender);
+        Ok(PendingCall { id, receiver })
+    }
+
+    fn register_callbacks(&self) -> Result<()> {
+        let mut registered = self.callbacks_registered.lock().unwrap();
+        if *registered {
+            return Ok(());
+        }
+        self.with_env(|env| {
+            let class = self.load_class(env, CALLBACK_CLASS)?;
+            let class = <&JClass>::from(class.as_obj());
+            env.register_native_methods(
+                class,
+                &[NativeMethod {
+                    name: "complete".into(),
+                    sig: "(JLjava/lang/String;Ljava/lang/String;)V".into(),
+                    fn_ptr: complete as *mut std::ffi::c_void,
+                }],
+            )?;
+            Ok(())
+        })?;
+        *registered = true;
+        Ok(())
+    }
+}
+
+/// Clears a pending exception and describes it.
+fn take_exception(env: &mut JNIEnv) -> Result<()> {
+    if !env.exception_check()? {
+        return Ok(());
+    }
+    let throwable = env.exception_occurred()?;
+    env.exception_clear()?;
+    let described = describe_exception(env, &throwable);
+    // Describing may throw in turn; that one is not worth reporting.
+    if env.exception_check()? {
+        env.exception_clear()?;
+    }
+    let (class, message) = described.unwrap_or_else(|_| ("java.lang.Throwable".to_string(), None));
+    Err(BridgeError::JavaException { class, message })
+}
+
+fn describe_exception(env: &mut JNIEnv, throwable: &JThrowable) -> Result<(String, Option<String>)> {
+    let class = env
+        .call_method(throwable, "getClass", "()Ljava/lang/Class;", &[])?
+        .l()?;
+    let name = env.call_method(&class, "getName", "()Ljava/lang/String;", &[])?.l()?;
+    let message = env
+        .call_method(throwable, "getMessage", "()Ljava/lang/String;", &[])?
+        .l()?;
+    Ok((
+        read_string(env, &JString::from(name)).unwrap_or_default(),
+        read_string(env, &JString::from(message)),
+    ))
+}
+
+fn read_string(env: &mut JNIEnv, string: &JString) -> Option<String> {
+    if string.is_null() {
+        return None;
+    }
+    env.get_string(string).ok().map(String::from)
+}
+
+/// Calls waiting for `NativeCallback.complete`, by id.
+#[derive(Default)]
+struct Callbacks {
+    next_id: jlong,
+    pending: HashMap<jlong, oneshot::Sender<Result<Option<String>>>>,
+}
+
+/// Never panics: it runs on a Java thread, where unwinding is undefined.
+fn callbacks() -> std::sync::MutexGuard<'static, Callbacks> {
+    CALLBACKS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// `NativeCallback.complete(long id, String result, String error)`. A non-null
+/// `error` fails the call; otherwise it resolves to `result`.
+extern "system" fn complete(mut env: JNIEnv, _class: JClass, id: jlong, result: JString, error: JString) {
+    let outcome = match read_string(&mut env, &error) {
+        Some(error) => Err(BridgeError::Callback(error)),
+        None => Ok(read_string(&mut env, &result)),
+    };
+    match callbacks().pending.remove(&id) {
+        Some(sender) => {
+            // The caller may have stopped waiting in the meantime.
+            let _ = sender.send(outcome);
+        }
+        None => log::warn!("Java completed unknown or abandoned call {id}"),
+    }
+}
+
+/// A Java call that answers through `NativeCallback.complete`. Resolves to the
+/// `result` string Java passed. Dropping it abandons the call.
+pub struct PendingCall {
+    id: jlong,
+    receiver: oneshot::Receiver<Result<Option<String>>>,
+}
+
+impl PendingCall {
+    /// The id to hand to Java.
+    pub fn id(&self) -> jlong {
+        self.id
+    }
+}
+
+impl Future for PendingCall {
+    type Output = Result<Option<String>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.receiver)
+            .poll(cx)
+            .map(|outcome| outcome.unwrap_or(Err(BridgeError::Dropped)))
+    }
+}
+
+impl Drop for PendingCall {
+    fn drop(&mut self) {
+        callbacks().pending.remove(&self.id);
+    }
+}
+
+#[cfg(all(test, feature = "jvm"))]
+mod tests {
+    use std::{path::PathBuf, process::Command};
+
+    use jni::{objects::JObject, InitArgsBuilder, JNIVersion};
+
+    use super::*;
+
+    /// Stands in for the Kotlin `NativeCallback` object, which has the same
+    /// static method but needs the Android toolchain to build.
+    const NATIVE_CALLBACK: &str = "package com.beout.app.bridge;
+
+public final class NativeCallback {
+    public static native void complete(long id, String result, String error);
+}
+";
+
+    /// The bridge to a JVM started for all tests, as a process can only start one.
+    fn bridge() -> &'static JniBridge {
+        static STARTED: OnceLock<&'static JniBridge> = OnceLock::new();
+        STARTED.get_or_init(|| {
+            let args = InitArgsBuilder::new()
+                .version(JNIVersion::V8)
+                .option(format!("-Djava.class.path={}", compile_native_callback().display()))
+                .build()
+                .unwrap();
+            JniBridge::install(JniBridge::new(JavaVM::new(args).unwrap()))
+        })
+    }
+
+    fn compile_native_callback() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("beout-jni-bridge-{}", std::process::id()));
+        let source = dir.join("NativeCallback.java");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&source, NATIVE_CALLBACK).unwrap();
+
+        let javac = std::env::var_os("JAVA_HOME")
+            .map(|home| PathBuf::from(home).join("bin").join("javac"))
+            .unwrap_or_else(|| "javac".into());
+        let status = Command::new(javac)
+            .arg("-d")
+            .arg(&dir)
+            .arg(&source)
+            .status()
+            .expect("javac runs");
+        assert!(status.success(), "javac failed on NativeCallback.java");
+        dir
+    }
+
+    /// Calls `NativeCallback.complete` from Java, as the Android side does.
+    fn complete_from_java(id: jlong, result: Option<&str>, error: Option<&str>) -> Result<()> {
+        let bridge = bridge();
+        bridge.with_env(|env| {
+            let class = bridge.load_class(env, CALLBACK_CLASS)?;
+            let result = match result {
+                Some(result) => env.new_string(result)?.into(),
+                None => JObject::null(),
+            };
+            let error = match error {
+                Some(error) => env.new_string(error)?.into(),
+                None => JObject::null(),
+            };
+            env.call_static_method(
+                <&JClass>::from(class.as_obj()),
+                "complete",
+                "(JLjava/lang/String;Ljava/lang/String;)V",
+                &[JValue::Long(id), JValue::Object(&result), JValue::Object(&error)],
+            )?;
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn hands_out_the_installed_bridge() {
+        assert!(std::ptr::eq(JniBridge::get().unwrap(), bridge()));
+    }
+
+    #[test]
+    fn reports_java_exceptions_with_their_class_and_message() {
+        let error = bridge()
+            .with_env(|env| {
+                env.throw_new("java/lang/IllegalStateException", "no account signed in")?;
+                Ok(())
+            })
+            .unwrap_err();
+        assert!(
+            matches!(
+                &error,
+                BridgeError::JavaException { class, message }
+                    if class == "java.lang.IllegalStateException"
+                        && message.as_deref() == Some("no account signed in")
+            ),
+            "{error:?}"
+        );
+        assert!(!bridge().with_env(|env| Ok(env.exception_check()?)).unwrap());
+    }
+
+    #[test]
+    fn reports_exceptions_thrown_by_java_code() {
+        let error = bridge()
+            .with_env(|env| {
+                let digits = env.new_string("twelve")?;
+                env.call_static_method(
+                    "java/lang/Integer",
+                    "parseInt",
+                    "(Ljava/lang/String;)I",
+                    &[JValue::Object(&digits)],
+                )?;
+                Ok(())
+            })
+            .unwrap_err();
+        assert!(
+            matches!(
+                &error,
+                BridgeError::JavaException { class, message }
+                    if class == "java.lang.NumberFormatException"
+                        && message.as_deref() == Some("For input string: \"twelve\"")
+            ),
+            "{error:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn resolves_pending_calls_through_native_callback() {
+        let succeeding = bridge().pending_call().unwrap();
+        let failing = bridge().pending_call().unwrap();
+        assert_ne!(succeeding.id(), failing.id());
+
+        complete_from_java(succeeding.id(), Some("signed in"), None).unwrap();
+        complete_from_java(failing.id(), None, Some("cancelled")).unwrap();
+        assert_eq!(succeeding.await.unwrap().as_deref(), Some("signed in"));
+        assert!(matches!(failing.await, Err(BridgeError::Callback(error)) if error == "cancelled"));
+    }
+
+    #[test]
+    fn dropping_a_pending_call_abandons_it() {
+        let call = bridge().pending_call().unwrap();
+        let id = call.id();
+        assert!(callbacks().pending.contains_key(&id));
+
+        drop(call);
+        assert!(!callbacks().pending.contains_key(&id));
+        // A late answer from Java is ignored rather than failing the Java side.
+        complete_from_java(id, Some("too late"), None).unwrap();
+    }
+}
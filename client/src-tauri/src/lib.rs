@@ -1,5 +1,17 @@
 // Learn more about Tauri commands at https://tauri.app/v1/guides/features/command
 
+use tauri::Manager;
+
+pub mod auth;
+#[cfg(any(target_os = "android", feature = "jvm"))]
+pub mod jni_bridge;
+#[cfg(target_os = "android")]
+pub mod mobile_google_auth;
+pub mod session;
+
+use auth::AuthState;
+use session::{PollingAuthFlow, SessionManager};
+
 #[tauri::command]
 fn greet(name: &str) -> String {
     format!("Hello, {}! You've been greeted from Rust!", name)
@@ -30,14 +42,33 @@ pub fn run() {
 
     let builder = tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
+        .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_deep_link::init());
-        // Google Auth plugin temporarily disabled
-        // .plugin(tauri_plugin_google_auth::init());
 
     log::info!("Plugins initialized, starting app...");
 
     builder
-        .invoke_handler(tauri::generate_handler![greet])
+        .setup(|app| {
+            let dir = app.path().app_data_dir()?.join("session");
+            let api_url = SessionManager::api_url_from_env();
+            app.manage(PollingAuthFlow::new(&api_url));
+            app.manage(SessionManager::new(api_url, &dir)?);
+            session::listen(app.handle());
+            app.manage(AuthState::default());
+            // Not on the builder, so a broken plugin cannot abort startup.
+            auth::init(app.handle());
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            greet,
+            auth::auth_diagnostics,
+            session::login_with_provider,
+            session::start_browser_sign_in,
+            session::start_polling_sign_in,
+            session::cancel_polling_sign_in,
+            session::current_session,
+            session::logout
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
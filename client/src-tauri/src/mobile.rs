@@ -1,2 +0,0 @@
-// Re-export the mobile Google auth functionality
-// pub mod mobile_google_auth;  // Temporarily disabled to prevent startup crashes
@@ -1 +0,0 @@
-// pub mod mobile_google_auth;  // Temporarily disabled to prevent startup crashes
@@ -1,3 +1,2 @@
-// pub mod google_auth;  // Temporarily disabled to prevent startup crashes
-
-// pub use google_auth::init as google_auth_plugin;  // Temporarily disabled
+// The in-tree google_auth plugin is superseded by tauri-plugin-google-auth,
+// registered in `auth::init`. Not compiled.
@@ -0,0 +1,63 @@
+use tauri::{command, AppHandle, Emitter, Runtime, State};
+use tauri_plugin_opener::OpenerExt;
+
+use super::{
+    BrowserSignIn, Error, PollingAuthFlow, ProviderCredential, Result, Session, SessionManager,
+    POLLING_EVENT,
+};
+
+#[command]
+pub async fn login_with_provider(
+    sessions: State<'_, SessionManager>,
+    credential: ProviderCredential,
+) -> Result<Session> {
+    sessions.login(credential).await
+}
+
+/// Starts a Google sign-in that completes through the `beout://auth` deep
+/// link. The caller opens `url` in the system browser.
+#[command]
+pub fn start_browser_sign_in(sessions: State<'_, SessionManager>) -> Result<BrowserSignIn> {
+    sessions.start_browser_sign_in()
+}
+
+/// Signs in through the server's polling routes, reporting progress as
+/// [`POLLING_EVENT`]. Resolves once the sign-in completed, failed, expired or
+/// was cancelled.
+#[command]
+pub async fn start_polling_sign_in<R: Runtime>(
+    app: AppHandle<R>,
+    sessions: State<'_, SessionManager>,
+    flow: State<'_, PollingAuthFlow>,
+) -> Result<Session> {
+    sessions
+        .sign_in_with_polling(
+            &flow,
+            |url| {
+                app.opener()
+                    .open_url(url, None::<&str>)
+                    .map_err(|e| Error::Browser(e.to_string()))
+            },
+            |progress| {
+                if let Err(e) = app.emit(POLLING_EVENT, progress) {
+                    log::error!("Failed to emit {POLLING_EVENT}: {e}");
+                }
+            },
+        )
+        .await
+}
+
+#[command]
+pub fn cancel_polling_sign_in(flow: State<'_, PollingAuthFlow>) {
+    flow.cancel();
+}
+
+#[command]
+pub async fn current_session(sessions: State<'_, SessionManager>) -> Result<Option<Session>> {
+    sessions.current().await
+}
+
+#[command]
+pub async fn logout(sessions: State<'_, SessionManager>) -> Result<()> {
+    sessions.logout().await
+}
@@ -0,0 +1,321 @@
+//! Google sign-in in the system browser, returning through `beout://auth`.
+//!
+//! The `state` and PKCE verifier of each attempt are sealed next to the
+//! session, so a callback still completes after the webview reloaded or the
+//! app was restarted while the browser was in front. Every `state` is single
+//! use and expires with the server's OAuth sessions.
+
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, Runtime, Url};
+use tauri_plugin_deep_link::DeepLinkExt;
+use tauri_plugin_google_auth::{random_token, Pkce};
+
+use super::{
+    store::SessionStore, unix_now, Error, ProviderCredential, Result, Session, SessionManager, GOOGLE_CLIENT_ID,
+};
+
+/// Emitted with a [`SignInEvent`] once a `beout://auth` callback was handled.
+pub const SIGN_IN_EVENT: &str = "session://sign-in";
+
+const AUTHORIZATION_ENDPOINT: &str = "https://accounts.google.com/o/oauth2/v2/auth";
+/// Google redirects to this page of the API server, which forwards the query
+/// to `beout://auth`.
+const REDIRECT_ROUTE: &str = "/auth/google/callback";
+const SCOPES: &str = "openid email profile";
+const CALLBACK_SCHEME: &str = "beout";
+const CALLBACK_HOST: &str = "auth";
+const PENDING_FILE: &str = "pending-sign-ins.bin";
+/// Seconds an attempt stays valid, as long as the server keeps OAuth sessions.
+const ATTEMPT_TTL: u64 = 10 * 60;
+/// Attempts kept at once; starting another drops the oldest.
+const MAX_PENDING: usize = 5;
+
+#[derive(Clone, Deserialize, Serialize)]
+struct PendingSignIn {
+    state: String,
+    code_verifier: String,
+    client_id: String,
+    redirect_uri: String,
+    created_at: u64,
+}
+
+/// Sign-in attempts waiting for their callback, mirrored to [`PENDING_FILE`].
+pub(super) struct PendingSignIns(Mutex<Vec<PendingSignIn>>);
+
+impl PendingSignIns {
+    pub fn load(store: &SessionStore) -> Self {
+        Self(Mutex::new(store.read_sealed(PENDING_FILE).unwrap_or_default()))
+    }
+}
+
+/// A started browser sign-in. `url` has to be opened in the system browser.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BrowserSignIn {
+    pub url: String,
+    pub expires_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum SignInEvent {
+    Completed { session: Session },
+    Failed { error: String },
+}
+
+/// Whether `url` is a sign-in callback, as opposed to another deep link.
+pub fn is_auth_callback(url: &Url) -> bool {
+    url.scheme() == CALLBACK_SCHEME && url.host_str() == Some(CALLBACK_HOST)
+}
+
+impl SessionManager {
+    /// Starts a browser sign-in with a fresh `state` and PKCE pair.
+    pub fn start_browser_sign_in(&self) -> Result<BrowserSignIn> {
+        let pkce = Pkce::new();
+        let attempt = PendingSignIn {
+            state: random_token(32),
+            code_verifier: pkce.verifier,
+            client_id: GOOGLE_CLIENT_ID.to_string(),
+            redirect_uri: format!("{}{REDIRECT_ROUTE}", self.api_url),
+            created_at: unix_now(),
+        };
+        let url = Url::parse_with_params(
+            AUTHORIZATION_ENDPOINT,
+            [
+                ("client_id", attempt.client_id.as_str()),
+                ("redirect_uri", attempt.redirect_uri.as_str()),
+                ("response_type", "code"),
+                ("scope", SCOPES),
+                ("state", attempt.state.as_str()),
+                ("code_challenge", pkce.challenge.as_str()),
+                ("code_challenge_method", "S256"),
+                ("prompt", "select_account"),
+            ],
+        )
+        .expect("authorization endpoint is a valid URL");
+
+        let expires_at = attempt.created_at + ATTEMPT_TTL;
+        self.update_pending(|pending| {
+            if pending.len() >= MAX_PENDING {
+                pending.remove(0);
+            }
+            pending.push(attempt);
+        })?;
+        Ok(BrowserSignIn {
+            url: url.into(),
+            expires_at,
+        })
+    }
+
+    /// Checks a `beout://auth` callback against the pending attempts and trades
+    /// its code for a session. The attempt is used up whatever the outcome.
+    pub async fn complete_browser_sign_in(&self, url: &Url) -> Result<Session> {
+        if !is_auth_callback(url) {
+            return Err(Error::InvalidCallback("not a beout://auth URL"));
+        }
+        let param = |name: &str| {
+            url.query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+        };
+        let state = param("state").ok_or(Error::InvalidCallback("no state"))?;
+
+        let mut attempt = None;
+        self.update_pending(|pending| {
+            if let Some(index) = pending.iter().position(|attempt| attempt.state == state) {
+                attempt = Some(pending.remove(index));
+            }
+        })?;
+        // Expired attempts were dropped on the way, so stale callbacks end here too.
+        let attempt = attempt.ok_or(Error::InvalidCallback("unknown, used or expired state"))?;
+        if let Some(error) = param("error") {
+            return Err(Error::Provider(error));
+        }
+        let code = param("code").ok_or(Error::InvalidCallback("no code"))?;
+
+        self.login(ProviderCredential::Google {
+            id_token: None,
+            code: Some(code),
+            code_verifier: Some(attempt.code_verifier),
+            redirect_uri: Some(attempt.redirect_uri),
+            client_id: Some(attempt.client_id),
+        })
+        .await
+    }
+
+    /// Applies `change` to the pending attempts, without the expired ones, and
+    /// persists the result.
+    fn update_pending(&self, change: impl FnOnce(&mut Vec<PendingSignIn>)) -> Result<()> {
+        let mut pending = self.pending.0.lock().unwrap();
+        let now = unix_now();
+        let mut updated: Vec<_> = pending
+            .iter()
+            .filter(|attempt| attempt.created_at + ATTEMPT_TTL > now)
+            .cloned()
+            .collect();
+        change(&mut updated);
+        self.store.write_sealed(PENDING_FILE, &updated)?;
+        *pending = updated;
+        Ok(())
+    }
+}
+
+/// Completes sign-ins from `beout://auth` links, both those opened while the
+/// app runs and the one it was launched with, and reports each through
+/// [`SIGN_IN_EVENT`].
+pub fn listen<R: Runtime>(app: &AppHandle<R>) {
+    let handle = app.clone();
+    app.deep_link().on_open_url(move |event| {
+        for url in event.urls() {
+            handle_url(&handle, url);
+        }
+    });
+
+    match app.deep_link().get_current() {
+        Ok(urls) => {
+            for url in urls.into_iter().flatten() {
+                handle_url(app, url);
+            }
+        }
+        Err(e) => log::warn!("Failed to read the launch deep link: {e}"),
+    }
+}
+
+fn handle_url<R: Runtime>(app: &AppHandle<R>, url: Url) {
+    if !is_auth_callback(&url) {
+        return;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let event = match app.state::<SessionManager>().complete_browser_sign_in(&url).await {
+            Ok(session) => SignInEvent::Completed { session },
+            Err(e) => {
+                log::warn!("Rejected sign-in callback: {e}");
+                SignInEvent::Failed { error: e.to_string() }
+            }
+        };
+        if let Err(e) = app.emit(SIGN_IN_EVENT, event) {
+            log::error!("Failed to emit {SIGN_IN_EVENT}: {e}");
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::{
+        test_support::{signed_in, temp_dir, Response, StandIn},
+        GOOGLE_TOKEN_ROUTE,
+    };
+
+    const EMAIL: &str = "ada@example.com";
+
+    async fn setup() -> (SessionManager, StandIn) {
+        let server = StandIn::start(|request| match request.path.as_str() {
+            GOOGLE_TOKEN_ROUTE => Response::json(signed_in(EMAIL)),
+            _ => Response::not_found(),
+        })
+        .await;
+        (SessionManager::new(&server.url, &temp_dir()).unwrap(), server)
+    }
+
+    /// Starts a sign-in and returns its `state`.
+    fn start(sessions: &SessionManager) -> String {
+        let started = Url::parse(&sessions.start_browser_sign_in().unwrap().url).unwrap();
+        let (_, state) = started.query_pairs().find(|(name, _)| name == "state").unwrap();
+        state.into_owned()
+    }
+
+    fn callback(query: &str) -> Url {
+        Url::parse(&format!("beout://auth?{query}")).unwrap()
+    }
+
+    fn is_invalid_callback(result: Result<Session>) -> bool {
+        matches!(result, Err(Error::InvalidCallback(_)))
+    }
+
+    #[tokio::test]
+    async fn redeems_the_code_with_the_stored_verifier() {
+        let (sessions, server) = setup().await;
+        let state = start(&sessions);
+        let attempt = sessions.pending.0.lock().unwrap()[0].clone();
+
+        let session = sessions
+            .complete_browser_sign_in(&callback(&format!("state={state}&code=4/approved")))
+            .await
+            .unwrap();
+        assert_eq!(session.user.email, EMAIL);
+        let body = &server.requests(GOOGLE_TOKEN_ROUTE)[0].body;
+        assert_eq!(body["code"], "4/approved");
+        assert_eq!(body["codeVerifier"], attempt.code_verifier);
+        assert_eq!(body["redirectUri"], format!("{}{REDIRECT_ROUTE}", server.url));
+        assert_eq!(body["clientId"], GOOGLE_CLIENT_ID);
+    }
+
+    #[tokio::test]
+    async fn rejects_an_unknown_state() {
+        let (sessions, server) = setup().await;
+        start(&sessions);
+
+        let forged = callback(&format!("state={}&code=4/forged", random_token(32)));
+        assert!(is_invalid_callback(sessions.complete_browser_sign_in(&forged).await));
+        assert!(is_invalid_callback(
+            sessions.complete_browser_sign_in(&callback("code=4/forged")).await
+        ));
+        assert_eq!(server.hits(GOOGLE_TOKEN_ROUTE), 0);
+    }
+
+    #[tokio::test]
+    async fn accepts_a_state_once() {
+        let (sessions, server) = setup().await;
+        let url = callback(&format!("state={}&code=4/approved", start(&sessions)));
+
+        sessions.complete_browser_sign_in(&url).await.unwrap();
+        assert!(is_invalid_callback(sessions.complete_browser_sign_in(&url).await));
+        assert_eq!(server.hits(GOOGLE_TOKEN_ROUTE), 1);
+    }
+
+    #[tokio::test]
+    async fn rejects_an_expired_attempt() {
+        let (sessions, server) = setup().await;
+        let state = start(&sessions);
+        sessions.pending.0.lock().unwrap()[0].created_at -= ATTEMPT_TTL;
+
+        let url = callback(&format!("state={state}&code=4/approved"));
+        assert!(is_invalid_callback(sessions.complete_browser_sign_in(&url).await));
+        assert_eq!(server.hits(GOOGLE_TOKEN_ROUTE), 0);
+    }
+
+    #[tokio::test]
+    async fn ignores_other_links_without_using_up_the_attempt() {
+        let (sessions, server) = setup().await;
+        let state = start(&sessions);
+
+        for other in ["https://be-out.app/auth", "beout://events", "evil://auth"] {
+            let url = Url::parse(&format!("{other}?state={state}&code=4/forged")).unwrap();
+            assert!(!is_auth_callback(&url));
+            assert!(is_invalid_callback(sessions.complete_browser_sign_in(&url).await));
+        }
+        assert_eq!(server.hits(GOOGLE_TOKEN_ROUTE), 0);
+
+        let url = callback(&format!("state={state}&code=4/approved"));
+        sessions.complete_browser_sign_in(&url).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn reports_a_provider_error_and_uses_up_the_attempt() {
+        let (sessions, server) = setup().await;
+        let state = start(&sessions);
+
+        let url = callback(&format!("state={state}&error=access_denied"));
+        assert!(matches!(
+            sessions.complete_browser_sign_in(&url).await,
+            Err(Error::Provider(error)) if error == "access_denied"
+        ));
+        let url = callback(&format!("state={state}&code=4/approved"));
+        assert!(is_invalid_callback(sessions.complete_browser_sign_in(&url).await));
+        assert_eq!(server.hits(GOOGLE_TOKEN_ROUTE), 0);
+    }
+}
@@ -0,0 +1,641 @@
+//! The Be Out session: provider credentials traded for a Be Out JWT, kept
+//! sealed on disk and renewed before it expires.
+//!
+//! Everything talks to the server at the URL given to [`SessionManager::new`],
+//! so the manager can be pointed at a local mock server that answers the
+//! routes below with `{ "token": "<jwt>", "user": { ... } }`.
+
+use std::{
+    path::Path,
+    sync::{Arc, RwLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tokio::sync::Mutex;
+
+pub use commands::*;
+pub use deep_link::{is_auth_callback, listen, BrowserSignIn, SignInEvent, SIGN_IN_EVENT};
+pub use polling::{Backoff, PollingAuthFlow, PollingProgress, POLLING_EVENT};
+
+mod commands;
+mod deep_link;
+mod polling;
+mod store;
+#[cfg(test)]
+mod test_support;
+
+use deep_link::PendingSignIns;
+use store::SessionStore;
+
+const DEFAULT_API_URL: &str = "http://localhost:3000";
+/// The Google OAuth client the server redeems authorization codes for, unless
+/// `VITE_GOOGLE_CLIENT_ID_ANDROID` names another one at build time.
+const GOOGLE_CLIENT_ID: &str = match option_env!("VITE_GOOGLE_CLIENT_ID_ANDROID") {
+    Some(client_id) => client_id,
+    None => "1064619689471-7lr8e71tr6h55as83o8gn4bdnhabavpu.apps.googleusercontent.com",
+};
+const GOOGLE_TOKEN_ROUTE: &str = "/api/auth/mobile/mobile/google/token";
+const GOOGLE_ID_TOKEN_ROUTE: &str = "/api/auth/google/validate";
+const APPLE_TOKEN_ROUTE: &str = "/api/auth/mobile/mobile/apple/token";
+/// Seconds before expiry from which the JWT is renewed.
+const REFRESH_MARGIN: u64 = 60 * 60;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("the server refused the sign-in ({status}): {message}")]
+    Rejected { status: u16, message: String },
+    #[error("invalid sign-in callback: {0}")]
+    InvalidCallback(&'static str),
+    #[error("the provider refused the sign-in: {0}")]
+    Provider(String),
+    #[error("failed to open the browser: {0}")]
+    Browser(String),
+    #[error("sign-in was cancelled")]
+    Cancelled,
+    #[error("sign-in expired before it was approved")]
+    Expired,
+    #[error("the credential has no {0}")]
+    MissingCredential(&'static str),
+    #[error("the API URL is not a valid URL")]
+    InvalidApiUrl,
+    #[error("invalid server response: {0}")]
+    InvalidResponse(&'static str),
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl Error {
+    /// The error of a non-success server response.
+    async fn rejected(response: reqwest::Response) -> Self {
+        let status = response.status();
+        let message = match response.json::<ErrorResponse>().await {
+            Ok(body) => body.message,
+            Err(_) => status.canonical_reason().unwrap_or("request failed").to_string(),
+        };
+        Self::Rejected {
+            status: status.as_u16(),
+            message,
+        }
+    }
+}
+
+impl Error {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::Rejected { .. } => ErrorCode::Rejected,
+            Self::InvalidCallback(_) => ErrorCode::InvalidCallback,
+            Self::Provider(_) => ErrorCode::Provider,
+            Self::Cancelled => ErrorCode::Cancelled,
+            Self::Expired => ErrorCode::Expired,
+            Self::MissingCredential(_) => ErrorCode::InvalidCredential,
+            Self::InvalidApiUrl => ErrorCode::Misconfigured,
+            Self::InvalidResponse(_) => ErrorCode::InvalidResponse,
+            Self::Http(_) => ErrorCode::Network,
+            Self::Browser(_) | Self::Io(_) => ErrorCode::Internal,
+        }
+    }
+
+    /// Extra context for the webview, when there is any.
+    pub fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            Self::Rejected { status, .. } => Some(serde_json::json!({ "status": status })),
+            Self::InvalidCallback(reason) | Self::InvalidResponse(reason) => {
+                Some(serde_json::json!({ "reason": reason }))
+            }
+            Self::MissingCredential(field) => Some(serde_json::json!({ "field": field })),
+            Self::Http(e) => e.status().map(|status| serde_json::json!({ "status": status.as_u16() })),
+            _ => None,
+        }
+    }
+}
+
+/// Stable, machine-readable error codes exposed to the webview.
+///
+/// Errors serialize as `{ code, message, details }`, like those of the
+/// google-auth plugin, so the webview handles both the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The server refused the credential or the session.
+    Rejected,
+    /// A `beout://auth` callback was malformed, unknown or expired.
+    InvalidCallback,
+    /// The identity provider refused the sign-in.
+    Provider,
+    Cancelled,
+    /// The sign-in was not approved in time.
+    Expired,
+    /// The provider credential lacks what the server needs.
+    InvalidCredential,
+    /// The API URL or client configuration is wrong.
+    Misconfigured,
+    /// The server answered with something unexpected.
+    InvalidResponse,
+    /// The server could not be reached.
+    Network,
+    Internal,
+}
+
+impl Serialize for Error {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Payload {
+            code: ErrorCode,
+            message: String,
+            details: Option<serde_json::Value>,
+        }
+
+        Payload {
+            code: self.code(),
+            message: self.to_string(),
+            details: self.details(),
+        }
+        .serialize(serializer)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Provider {
+    Google,
+    Apple,
+}
+
+/// What an identity provider handed the app, for the server to trade for a JWT.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "provider", rename_all = "lowercase")]
+pub enum ProviderCredential {
+    /// A Google authorization code is redeemed by the server with its PKCE
+    /// verifier. Without one, the ID token is verified instead.
+    #[serde(rename_all = "camelCase")]
+    Google {
+        id_token: Option<String>,
+        code: Option<String>,
+        code_verifier: Option<String>,
+        redirect_uri: Option<String>,
+        client_id: Option<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Apple {
+        identity_token: String,
+        authorization_code: Option<String>,
+    },
+}
+
+impl ProviderCredential {
+    pub fn provider(&self) -> Provider {
+        match self {
+            Self::Google { .. } => Provider::Google,
+            Self::Apple { .. } => Provider::Apple,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct User {
+    #[serde(deserialize_with = "string_or_number")]
+    pub id: String,
+    pub email: String,
+    #[serde(default)]
+    pub role: Option<String>,
+}
+
+/// A signed-in Be Out session.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    /// JWT to send as `Authorization: Bearer` to the Be Out API.
+    pub token: String,
+    pub user: User,
+    pub provider: Provider,
+    /// Unix time at which the JWT expires, when it says.
+    pub expires_at: Option<u64>,
+}
+
+impl Session {
+    /// A session for a freshly issued `token`.
+    fn new(token: String, user: User, provider: Provider) -> Result<Self> {
+        Ok(Self {
+            expires_at: jwt_expiry(&token)?,
+            token,
+            user,
+            provider,
+        })
+    }
+
+    fn expires_within(&self, margin: u64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= unix_now() + margin)
+    }
+}
+
+/// Supplies a new provider credential without user interaction, e.g. a fresh
+/// Google ID token from the google-auth plugin. The server has no refresh
+/// route, so this is how an expiring JWT gets renewed.
+#[async_trait]
+pub trait CredentialSource: Send + Sync {
+    async fn refresh(&self, provider: Provider) -> Option<ProviderCredential>;
+}
+
+/// Both token routes answer with the JWT; `/mobile/*/token` nests the user,
+/// `/google/validate` flattens it.
+#[derive(Deserialize)]
+struct TokenResponse {
+    token: String,
+    #[serde(default)]
+    user: Option<User>,
+    #[serde(default, deserialize_with = "optional_string_or_number")]
+    user_id: Option<String>,
+    #[serde(default)]
+    email: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct JwtClaims {
+    #[serde(default)]
+    exp: Option<u64>,
+}
+
+pub struct SessionManager {
+    api_url: String,
+    http: reqwest::Client,
+    store: SessionStore,
+    pending: PendingSignIns,
+    credentials: RwLock<Option<Arc<dyn CredentialSource>>>,
+    /// Held across logins and refreshes so a slow refresh cannot overwrite a
+    /// newer sign-in.
+    exchange: Mutex<()>,
+}
+
+impl SessionManager {
+    /// Manager for the server at `api_url`, keeping the session in `dir`.
+    pub fn new(api_url: impl Into<String>, dir: &Path) -> Result<Self> {
+        let store = SessionStore::open(dir)?;
+        Ok(Self {
+            api_url: api_url.into().trim_end_matches('/').to_string(),
+            http: reqwest::Client::new(),
+            pending: PendingSignIns::load(&store),
+            store,
+            credentials: RwLock::new(None),
+            exchange: Mutex::new(()),
+        })
+    }
+
+    /// `BEOUT_API_URL` at runtime, else `VITE_API_URL` at build time, else the
+    /// local development server.
+    pub fn api_url_from_env() -> String {
+        std::env::var("BEOUT_API_URL")
+            .ok()
+            .or_else(|| option_env!("VITE_API_URL").map(String::from))
+            .unwrap_or_else(|| DEFAULT_API_URL.to_string())
+    }
+
+    pub fn set_credential_source(&self, source: Arc<dyn CredentialSource>) {
+        *self.credentials.write().unwrap() = Some(source);
+    }
+
+    /// Trades `credential` for a JWT and makes it the current session.
+    pub async fn login(&self, credential: ProviderCredential) -> Result<Session> {
+        let _exchange = self.exchange.lock().await;
+        let session = self.exchange(&credential).await?;
+        self.store.set(session.clone())?;
+        Ok(session)
+    }
+
+    /// The current session, renewed first when it is about to expire. An expired
+    /// session that cannot be renewed is dropped.
+    pub async fn current(&self) -> Result<Option<Session>> {
+        let Some(session) = self.store.get() else {
+            return Ok(None);
+        };
+        if !session.expires_within(REFRESH_MARGIN) {
+            return Ok(Some(session));
+        }
+
+        let _exchange = self.exchange.lock().await;
+        // Another caller may have renewed it, or logged out, while we waited.
+        let Some(session) = self.store.get() else {
+            return Ok(None);
+        };
+        if !session.expires_within(REFRESH_MARGIN) {
+            return Ok(Some(session));
+        }
+
+        match self.refresh(session.provider).await {
+            Ok(Some(renewed)) => {
+                self.store.set(renewed.clone())?;
+                return Ok(Some(renewed));
+            }
+            Ok(None) => {}
+            Err(e) => log::warn!("Failed to renew the Be Out session: {e}"),
+        }
+
+        if session.expires_within(0) {
+            self.store.clear()?;
+            return Ok(None);
+        }
+        Ok(Some(session))
+    }
+
+    /// Signs in through `flow` and makes the result the current session. See
+    /// [`PollingAuthFlow::run`].
+    pub async fn sign_in_with_polling(
+        &self,
+        flow: &PollingAuthFlow,
+        open_url: impl FnOnce(&str) -> Result<()>,
+        progress: impl Fn(PollingProgress),
+    ) -> Result<Session> {
+        let session = flow.run(open_url, progress).await?;
+        let _exchange = self.exchange.lock().await;
+        self.store.set(session.clone())?;
+        Ok(session)
+    }
+
+    pub async fn logout(&self) -> Result<()> {
+        let _exchange = self.exchange.lock().await;
+        self.store.clear()?;
+        Ok(())
+    }
+
+    async fn refresh(&self, provider: Provider) -> Result<Option<Session>> {
+        let source = self.credentials.read().unwrap().clone();
+        let Some(source) = source else {
+            return Ok(None);
+        };
+        match source.refresh(provider).await {
+            Some(credential) => self.exchange(&credential).await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    async fn exchange(&self, credential: &ProviderCredential) -> Result<Session> {
+        let (route, body) = match credential {
+            ProviderCredential::Google {
+                code: Some(code),
+                code_verifier,
+                redirect_uri,
+                client_id,
+                ..
+            } => {
+                let code_verifier = code_verifier
+                    .as_deref()
+                    .ok_or(Error::MissingCredential("codeVerifier"))?;
+                (
+                    GOOGLE_TOKEN_ROUTE,
+                    serde_json::json!({
+                        "code": code,
+                        "codeVerifier": code_verifier,
+                        "redirectUri": redirect_uri,
+                        "clientId": client_id,
+                    }),
+                )
+            }
+            ProviderCredential::Google { id_token, .. } => {
+                let id_token = id_token.as_deref().ok_or(Error::MissingCredential("idToken"))?;
+                (GOOGLE_ID_TOKEN_ROUTE, serde_json::json!({ "idToken": id_token }))
+            }
+            ProviderCredential::Apple {
+                identity_token,
+                authorization_code,
+            } => (
+                APPLE_TOKEN_ROUTE,
+                serde_json::json!({
+                    "identityToken": identity_token,
+                    "authorizationCode": authorization_code,
+                }),
+            ),
+        };
+
+        let response = self
+            .http
+            .post(format!("{}{route}", self.api_url))
+            .json(&body)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(Error::rejected(response).await);
+        }
+
+        let response: TokenResponse = response.json().await?;
+        let user = match response.user {
+            Some(user) => user,
+            None => User {
+                id: response.user_id.ok_or(Error::InvalidResponse("no user"))?,
+                email: response.email.ok_or(Error::InvalidResponse("no user email"))?,
+                role: None,
+            },
+        };
+        Session::new(response.token, user, credential.provider())
+    }
+}
+
+/// `exp` of a JWT, read without verifying it: the server that just issued the
+/// token is the one that checks it.
+fn jwt_expiry(token: &str) -> Result<Option<u64>> {
+    let payload = token
+        .split('.')
+        .nth(1)
+        .ok_or(Error::InvalidResponse("token is not a JWT"))?;
+    let payload = URL_SAFE_NO_PAD
+        .decode(payload)
+        .map_err(|_| Error::InvalidResponse("token is not a JWT"))?;
+    let claims: JwtClaims =
+        serde_json::from_slice(&payload).map_err(|_| Error::InvalidResponse("token is not a JWT"))?;
+    Ok(claims.exp)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// User IDs are numbers or strings depending on the route.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StringOrNumber {
+    String(String),
+    Number(serde_json::Number),
+}
+
+impl From<StringOrNumber> for String {
+    fn from(value: StringOrNumber) -> Self {
+        match value {
+            StringOrNumber::String(s) => s,
+            StringOrNumber::Number(n) => n.to_string(),
+        }
+    }
+}
+
+fn string_or_number<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<String, D::Error> {
+    StringOrNumber::deserialize(deserializer).map(String::from)
+}
+
+fn optional_string_or_number<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<String>, D::Error> {
+    Option::<StringOrNumber>::deserialize(deserializer).map(|value| value.map(String::from))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use serde_json::json;
+
+    use super::*;
+    use test_support::{signed_in, temp_dir, token, Response, StandIn};
+
+    const EMAIL: &str = "ada@example.com";
+
+    /// A Be Out server answering every token route.
+    async fn server() -> StandIn {
+        StandIn::start(|request| match request.path.as_str() {
+            GOOGLE_TOKEN_ROUTE | APPLE_TOKEN_ROUTE => Response::json(signed_in(EMAIL)),
+            GOOGLE_ID_TOKEN_ROUTE => {
+                let signed_in = signed_in(EMAIL);
+                Response::json(json!({ "token": signed_in["token"], "user_id": 7, "email": EMAIL }))
+            }
+            _ => Response::not_found(),
+        })
+        .await
+    }
+
+    fn google_code() -> ProviderCredential {
+        ProviderCredential::Google {
+            id_token: None,
+            code: Some("4/code".into()),
+            code_verifier: Some("verifier".into()),
+            redirect_uri: Some("https://be-out.app/auth/google/callback".into()),
+            client_id: Some(GOOGLE_CLIENT_ID.into()),
+        }
+    }
+
+    fn google_id_token() -> ProviderCredential {
+        ProviderCredential::Google {
+            id_token: Some("google-id-token".into()),
+            code: None,
+            code_verifier: None,
+            redirect_uri: None,
+            client_id: None,
+        }
+    }
+
+    /// Hands out a fresh Google ID token whenever the session needs renewing.
+    struct Renewer;
+
+    #[async_trait]
+    impl CredentialSource for Renewer {
+        async fn refresh(&self, _provider: Provider) -> Option<ProviderCredential> {
+            Some(google_id_token())
+        }
+    }
+
+    #[tokio::test]
+    async fn trades_google_and_apple_credentials() {
+        let server = server().await;
+        let sessions = SessionManager::new(&server.url, &temp_dir()).unwrap();
+
+        let session = sessions.login(google_code()).await.unwrap();
+        assert_eq!((session.user.email.as_str(), session.provider), (EMAIL, Provider::Google));
+        let body = &server.requests(GOOGLE_TOKEN_ROUTE)[0].body;
+        assert_eq!(body["code"], "4/code");
+        assert_eq!(body["codeVerifier"], "verifier");
+        assert_eq!(body["redirectUri"], "https://be-out.app/auth/google/callback");
+
+        let session = sessions.login(google_id_token()).await.unwrap();
+        assert_eq!((session.user.id.as_str(), session.user.email.as_str()), ("7", EMAIL));
+        assert_eq!(server.requests(GOOGLE_ID_TOKEN_ROUTE)[0].body["idToken"], "google-id-token");
+
+        let session = sessions
+            .login(ProviderCredential::Apple {
+                identity_token: "apple-identity-token".into(),
+                authorization_code: Some("apple-code".into()),
+            })
+            .await
+            .unwrap();
+        assert_eq!(session.provider, Provider::Apple);
+        let body = &server.requests(APPLE_TOKEN_ROUTE)[0].body;
+        assert_eq!(body["identityToken"], "apple-identity-token");
+        assert_eq!(body["authorizationCode"], "apple-code");
+        assert_eq!(sessions.current().await.unwrap().unwrap().provider, Provider::Apple);
+    }
+
+    #[tokio::test]
+    async fn renews_a_session_within_the_refresh_margin() {
+        // The first token expires in ten minutes, the renewed ones in a day.
+        let logins = AtomicUsize::new(0);
+        let server = StandIn::start(move |request| match request.path.as_str() {
+            GOOGLE_ID_TOKEN_ROUTE => {
+                let lifetime = match logins.fetch_add(1, Ordering::SeqCst) {
+                    0 => 10 * 60,
+                    _ => 24 * 60 * 60,
+                };
+                Response::json(json!({ "token": token(EMAIL, lifetime), "user_id": 7, "email": EMAIL }))
+            }
+            _ => Response::not_found(),
+        })
+        .await;
+        let sessions = SessionManager::new(&server.url, &temp_dir()).unwrap();
+        let expiring = sessions.login(google_id_token()).await.unwrap();
+
+        // Without a credential source the session is kept until it expires.
+        let current = sessions.current().await.unwrap().unwrap();
+        assert_eq!(current.token, expiring.token);
+
+        sessions.set_credential_source(Arc::new(Renewer));
+        let renewed = sessions.current().await.unwrap().unwrap();
+        assert_ne!(renewed.token, expiring.token);
+        assert!(!renewed.expires_within(REFRESH_MARGIN));
+        assert_eq!(server.hits(GOOGLE_ID_TOKEN_ROUTE), 2);
+
+        // Renewed sessions are not renewed again.
+        sessions.current().await.unwrap();
+        assert_eq!(server.hits(GOOGLE_ID_TOKEN_ROUTE), 2);
+    }
+
+    #[tokio::test]
+    async fn reports_rejections_as_code_message_and_details() {
+        let server = StandIn::start(|_| Response::json(json!({ "message": "invalid grant" })).status(401)).await;
+        let sessions = SessionManager::new(&server.url, &temp_dir()).unwrap();
+
+        let error = sessions.login(google_code()).await.unwrap_err();
+        assert!(matches!(error, Error::Rejected { status: 401, .. }));
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            json!({
+                "code": "rejected",
+                "message": "the server refused the sign-in (401): invalid grant",
+                "details": { "status": 401 },
+            })
+        );
+        assert!(sessions.current().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn logout_clears_the_sealed_store() {
+        let server = server().await;
+        let dir = temp_dir();
+        let sessions = SessionManager::new(&server.url, &dir).unwrap();
+        sessions.login(google_code()).await.unwrap();
+        assert!(SessionManager::new(&server.url, &dir).unwrap().current().await.unwrap().is_some());
+
+        sessions.logout().await.unwrap();
+        assert!(sessions.current().await.unwrap().is_none());
+        assert!(!dir.join("session.bin").exists());
+        assert!(SessionManager::new(&server.url, &dir).unwrap().current().await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,431 @@
+//! Sign-in through the server's polling routes, for platforms where the
+//! browser cannot hand the result back to the app.
+//!
+//! The app registers a challenge with the server, opens the browser at
+//! `/mobile/start`, and polls `/mobile/poll/:challenge` until the server has
+//! seen Google's callback. Polls back off exponentially with jitter so a
+//! crowd of clients waiting on slow approvals does not hit the server in step.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tauri_plugin_google_auth::random_token;
+use tokio::sync::oneshot;
+
+use super::{Error, Provider, Result, Session, TokenResponse, User, GOOGLE_CLIENT_ID};
+
+/// Emitted with a [`PollingProgress`] at every step of a polling sign-in.
+pub const POLLING_EVENT: &str = "session://polling";
+
+const SESSION_ROUTE: &str = "/api/auth/mobile/mobile/session";
+const START_ROUTE: &str = "/api/auth/mobile/mobile/start";
+const POLL_ROUTE: &str = "/api/auth/mobile/mobile/poll";
+const EXCHANGE_ROUTE: &str = "/api/auth/mobile/mobile/exchange";
+/// Where `/mobile/start` sends Google's response, needed to redeem a code.
+const REDIRECT_ROUTE: &str = "/auth/mobile/google/callback";
+/// How long the server keeps a challenge.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Delays between polls: `initial * factor^n`, capped at `max`, each scaled by
+/// a random factor within `1 ± jitter`.
+#[derive(Debug, Clone, Copy)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+    pub factor: f64,
+    pub jitter: f64,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(15),
+            factor: 2.0,
+            jitter: 0.2,
+        }
+    }
+}
+
+impl Backoff {
+    fn delay(&self, attempt: u32) -> Duration {
+        let base = self
+            .initial
+            .mul_f64(self.factor.powi(attempt.min(32) as i32))
+            .min(self.max);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        base.mul_f64(rand::thread_rng().gen_range(1.0 - jitter..=1.0 + jitter))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum PollingProgress {
+    /// The browser was sent to `url`.
+    #[serde(rename_all = "camelCase")]
+    Started { url: String },
+    /// Not approved yet; the next poll runs in `next_poll_ms`.
+    #[serde(rename_all = "camelCase")]
+    Pending { attempt: u32, next_poll_ms: u64 },
+    /// Approved; the authorization code is being redeemed.
+    Exchanging,
+    Completed { session: Session },
+    Expired,
+    Cancelled,
+    Failed { error: String },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+enum PollResponse {
+    Pending,
+    /// The server either finished the sign-in itself or hands over the code.
+    Completed {
+        #[serde(default)]
+        token: Option<String>,
+        #[serde(default)]
+        user: Option<User>,
+        #[serde(default)]
+        code: Option<String>,
+    },
+    Error {
+        #[serde(default)]
+        error: Option<String>,
+    },
+    Expired,
+}
+
+/// One sign-in at a time through the polling routes of the server at
+/// `api_url`. Starting another run cancels the one in progress.
+pub struct PollingAuthFlow {
+    api_url: String,
+    http: reqwest::Client,
+    client_id: String,
+    redirect_uri: String,
+    backoff: Backoff,
+    timeout: Duration,
+    /// Dropped to cancel the running sign-in.
+    cancel: Mutex<Option<oneshot::Sender<()>>>,
+}
+
+impl PollingAuthFlow {
+    pub fn new(api_url: impl Into<String>) -> Self {
+        let api_url = api_url.into().trim_end_matches('/').to_string();
+        Self {
+            redirect_uri: format!("{api_url}{REDIRECT_ROUTE}"),
+            api_url,
+            http: reqwest::Client::new(),
+            client_id: GOOGLE_CLIENT_ID.to_string(),
+            backoff: Backoff::default(),
+            timeout: DEFAULT_TIMEOUT,
+            cancel: Mutex::new(None),
+        }
+    }
+
+    pub fn backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// How long to wait for approval. Defaults to the server's ten minutes.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Overrides the redirect URI, which defaults to the callback page of the
+    /// server at `api_url`.
+    pub fn redirect_uri(mut self, redirect_uri: impl Into<String>) -> Self {
+        self.redirect_uri = redirect_uri.into();
+        self
+    }
+
+    /// Cancels the running sign-in, if any.
+    pub fn cancel(&self) {
+        self.cancel.lock().unwrap().take();
+    }
+
+    /// Registers a challenge, passes the start URL to `open_url` and polls until
+    /// the sign-in completes, fails, expires or is cancelled. Every step is
+    /// reported to `progress`, the outcome included.
+    pub async fn run(
+        &self,
+        open_url: impl FnOnce(&str) -> Result<()>,
+        progress: impl Fn(PollingProgress),
+    ) -> Result<Session> {
+        let (cancel, cancelled) = oneshot::channel::<()>();
+        *self.cancel.lock().unwrap() = Some(cancel);
+
+        // Dropping `poll` on cancellation also abandons an in-flight request,
+        // be it a poll or the code exchange.
+        let result = tokio::select! {
+            result = self.poll(open_url, &progress) => result,
+            _ = cancelled => Err(Error::Cancelled),
+        };
+        progress(match &result {
+            Ok(session) => PollingProgress::Completed {
+                session: session.clone(),
+            },
+            Err(Error::Expired) => PollingProgress::Expired,
+            Err(Error::Cancelled) => PollingProgress::Cancelled,
+            Err(e) => PollingProgress::Failed { error: e.to_string() },
+        });
+        result
+    }
+
+    async fn poll(
+        &self,
+        open_url: impl FnOnce(&str) -> Result<()>,
+        progress: &impl Fn(PollingProgress),
+    ) -> Result<Session> {
+        let deadline = Instant::now() + self.timeout;
+        let challenge = random_token(32);
+        let code_verifier = random_token(32);
+
+        let response = self
+            .http
+            .post(format!("{}{SESSION_ROUTE}", self.api_url))
+            .json(&serde_json::json!({
+                "challenge": challenge,
+                "codeVerifier": code_verifier,
+                "clientId": self.client_id,
+            }))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(Error::rejected(response).await);
+        }
+
+        let url = reqwest::Url::parse_with_params(
+            &format!("{}{START_ROUTE}", self.api_url),
+            [("session", random_token(32).as_str()), ("challenge", challenge.as_str())],
+        )
+        .map_err(|_| Error::InvalidApiUrl)?;
+        open_url(url.as_str())?;
+        progress(PollingProgress::Started { url: url.into() });
+
+        let poll_url = format!("{}{POLL_ROUTE}/{challenge}", self.api_url);
+        let mut attempt = 0;
+        loop {
+            let delay = self.backoff.delay(attempt);
+            if Instant::now() + delay >= deadline {
+                return Err(Error::Expired);
+            }
+            progress(PollingProgress::Pending {
+                attempt,
+                next_poll_ms: delay.as_millis() as u64,
+            });
+            attempt += 1;
+            tokio::time::sleep(delay).await;
+
+            let response = match self.http.get(&poll_url).send().await {
+                Ok(response) if response.status().is_server_error() => {
+                    log::warn!("Sign-in poll failed with {}, retrying", response.status());
+                    continue;
+                }
+                Ok(response) if !response.status().is_success() => {
+                    return Err(Error::rejected(response).await);
+                }
+                Ok(response) => response,
+                // The network may come and go while the user is in the browser.
+                Err(e) => {
+                    log::warn!("Sign-in poll failed, retrying: {e}");
+                    continue;
+                }
+            };
+
+            match response.json::<PollResponse>().await? {
+                PollResponse::Pending => {}
+                PollResponse::Expired => return Err(Error::Expired),
+                PollResponse::Error { error } => {
+                    return Err(Error::Provider(
+                        error.unwrap_or_else(|| "sign-in failed".to_string()),
+                    ))
+                }
+                PollResponse::Completed {
+                    token: Some(token),
+                    user: Some(user),
+                    ..
+                } => return Session::new(token, user, Provider::Google),
+                PollResponse::Completed { code: Some(code), .. } => {
+                    progress(PollingProgress::Exchanging);
+                    return self.exchange(&code, &code_verifier).await;
+                }
+                PollResponse::Completed { .. } => {
+                    return Err(Error::InvalidResponse("completed sign-in has neither token nor code"))
+                }
+            }
+        }
+    }
+
+    async fn exchange(&self, code: &str, code_verifier: &str) -> Result<Session> {
+        let response = self
+            .http
+            .post(format!("{}{EXCHANGE_ROUTE}", self.api_url))
+            .json(&serde_json::json!({
+                "code": code,
+                "codeVerifier": code_verifier,
+                "redirectUri": self.redirect_uri,
+            }))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(Error::rejected(response).await);
+        }
+        let response: TokenResponse = response.json().await?;
+        Session::new(
+            response.token,
+            response.user.ok_or(Error::InvalidResponse("no user"))?,
+            Provider::Google,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::session::test_support::{signed_in, Response, StandIn};
+
+    const EMAIL: &str = "ada@example.com";
+    /// Longer than any test may take.
+    const HANG: Duration = Duration::from_secs(60);
+
+    /// A server whose `n`th poll, from 0, is answered by `poll(n)`.
+    async fn server(poll: impl Fn(usize) -> Response + Send + Sync + 'static) -> StandIn {
+        let polls = AtomicUsize::new(0);
+        StandIn::start(move |request| match request.path.as_str() {
+            SESSION_ROUTE => Response::json(json!({ "success": true })),
+            EXCHANGE_ROUTE => Response::json(signed_in(EMAIL)),
+            path if path.starts_with(POLL_ROUTE) => poll(polls.fetch_add(1, Ordering::SeqCst)),
+            _ => Response::not_found(),
+        })
+        .await
+    }
+
+    fn flow(server: &StandIn) -> PollingAuthFlow {
+        PollingAuthFlow::new(&server.url).backoff(Backoff {
+            initial: Duration::from_millis(10),
+            max: Duration::from_millis(10),
+            factor: 1.0,
+            jitter: 0.0,
+        })
+    }
+
+    /// Runs `flow` to the end, with the `status` of every progress report.
+    async fn sign_in(flow: &PollingAuthFlow) -> (Result<Session>, Vec<String>) {
+        let statuses = Mutex::new(Vec::new());
+        let result = flow
+            .run(
+                |_| Ok(()),
+                |progress| {
+                    let progress = serde_json::to_value(progress).unwrap();
+                    statuses.lock().unwrap().push(progress["status"].as_str().unwrap().to_string());
+                },
+            )
+            .await;
+        (result, statuses.into_inner().unwrap())
+    }
+
+    /// Cancels `flow` once `server` saw a request for `path`.
+    async fn cancel_during(flow: &PollingAuthFlow, server: &StandIn, path: &str) {
+        while server.hits(path) == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        flow.cancel();
+    }
+
+    #[tokio::test]
+    async fn redeems_the_code_once_approved() {
+        let server = server(|n| match n {
+            0 => Response::json(json!({ "status": "pending" })),
+            _ => Response::json(json!({ "status": "completed", "code": "4/approved" })),
+        })
+        .await;
+
+        let (session, statuses) = sign_in(&flow(&server)).await;
+        assert_eq!(session.unwrap().user.email, EMAIL);
+        assert_eq!(statuses, ["started", "pending", "pending", "exchanging", "completed"]);
+
+        let registered = &server.requests(SESSION_ROUTE)[0].body;
+        let exchanged = &server.requests(EXCHANGE_ROUTE)[0].body;
+        assert_eq!(exchanged["code"], "4/approved");
+        assert_eq!(exchanged["codeVerifier"], registered["codeVerifier"]);
+        assert_eq!(exchanged["redirectUri"], format!("{}{REDIRECT_ROUTE}", server.url));
+        assert_eq!(registered["clientId"], GOOGLE_CLIENT_ID);
+    }
+
+    #[tokio::test]
+    async fn stops_when_the_challenge_expires() {
+        let server = server(|n| match n {
+            0 => Response::json(json!({ "status": "pending" })),
+            _ => Response::json(json!({ "status": "expired" })),
+        })
+        .await;
+
+        let (session, statuses) = sign_in(&flow(&server)).await;
+        assert!(matches!(session, Err(Error::Expired)));
+        assert_eq!(statuses, ["started", "pending", "pending", "expired"]);
+        assert_eq!(server.hits(EXCHANGE_ROUTE), 0);
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors() {
+        let server = server(|n| match n {
+            0 => Response::json(json!({ "message": "unavailable" })).status(503),
+            _ => {
+                let mut body = signed_in(EMAIL);
+                body["status"] = json!("completed");
+                Response::json(body)
+            }
+        })
+        .await;
+
+        let (session, statuses) = sign_in(&flow(&server)).await;
+        assert_eq!(session.unwrap().user.email, EMAIL);
+        assert_eq!(statuses, ["started", "pending", "pending", "completed"]);
+        assert_eq!(server.hits(POLL_ROUTE), 2);
+    }
+
+    #[tokio::test]
+    async fn cancels_a_poll_in_flight() {
+        let server = server(|_| Response::json(json!({ "status": "pending" })).delay(HANG)).await;
+        let flow = flow(&server);
+
+        let ((session, statuses), ()) = tokio::time::timeout(
+            Duration::from_secs(5),
+            async { tokio::join!(sign_in(&flow), cancel_during(&flow, &server, POLL_ROUTE)) },
+        )
+        .await
+        .expect("cancelling does not wait for the poll");
+        assert!(matches!(session, Err(Error::Cancelled)));
+        assert_eq!(statuses.last().unwrap(), "cancelled");
+    }
+
+    #[tokio::test]
+    async fn cancels_an_exchange_in_flight() {
+        let server = StandIn::start(|request| match request.path.as_str() {
+            SESSION_ROUTE => Response::json(json!({ "success": true })),
+            EXCHANGE_ROUTE => Response::json(signed_in(EMAIL)).delay(HANG),
+            _ => Response::json(json!({ "status": "completed", "code": "4/approved" })),
+        })
+        .await;
+        let flow = flow(&server);
+
+        let ((session, statuses), ()) = tokio::time::timeout(
+            Duration::from_secs(5),
+            async { tokio::join!(sign_in(&flow), cancel_during(&flow, &server, EXCHANGE_ROUTE)) },
+        )
+        .await
+        .expect("cancelling does not wait for the exchange");
+        assert!(matches!(session, Err(Error::Cancelled)));
+        assert_eq!(statuses, ["started", "pending", "exchanging", "cancelled"]);
+    }
+}
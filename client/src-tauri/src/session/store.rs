@@ -0,0 +1,62 @@
+use std::{io, path::Path, sync::Mutex};
+
+use serde::{de::DeserializeOwned, Serialize};
+use tauri_plugin_google_auth::SealedDir;
+
+use super::Session;
+
+const SESSION_FILE: &str = "session.bin";
+
+/// The Be Out session, sealed in the app data dir with the google-auth plugin's
+/// [`SealedDir`].
+pub(crate) struct SessionStore {
+    sealed: SealedDir,
+    session: Mutex<Option<Session>>,
+}
+
+impl SessionStore {
+    /// Opens the store in `dir`, creating the key on first use. A session that
+    /// cannot be decrypted is discarded.
+    pub fn open(dir: &Path) -> io::Result<Self> {
+        let sealed = SealedDir::open(dir)?;
+        let session = match sealed.read::<Session>(SESSION_FILE) {
+            Ok(session) => session,
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                log::warn!("Discarding unreadable Be Out session");
+                let _ = sealed.remove(SESSION_FILE);
+                None
+            }
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self {
+            sealed,
+            session: Mutex::new(session),
+        })
+    }
+
+    pub fn get(&self) -> Option<Session> {
+        self.session.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, session: Session) -> io::Result<()> {
+        self.sealed.write(SESSION_FILE, &session)?;
+        *self.session.lock().unwrap() = Some(session);
+        Ok(())
+    }
+
+    pub fn clear(&self) -> io::Result<()> {
+        self.sealed.remove(SESSION_FILE)?;
+        *self.session.lock().unwrap() = None;
+        Ok(())
+    }
+
+    /// Reads `file` next to the session, sealed with the same key.
+    pub fn read_sealed<T: DeserializeOwned>(&self, file: &str) -> Option<T> {
+        self.sealed.read(file).ok().flatten()
+    }
+
+    pub fn write_sealed<T: Serialize>(&self, file: &str, value: &T) -> io::Result<()> {
+        self.sealed.write(file, value)
+    }
+}
@@ -0,0 +1,176 @@
+//! A local stand-in for the Be Out server, for the unit tests.
+
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+/// A request received by a [`StandIn`].
+#[derive(Debug, Clone)]
+pub(crate) struct Request {
+    pub path: String,
+    /// JSON body, `null` for a `GET`.
+    pub body: Value,
+}
+
+pub(crate) struct Response {
+    status: u16,
+    body: String,
+    delay: Duration,
+}
+
+impl Response {
+    pub fn json(body: Value) -> Self {
+        Self {
+            status: 200,
+            body: body.to_string(),
+            delay: Duration::ZERO,
+        }
+    }
+
+    pub fn not_found() -> Self {
+        Self::json(json!({ "message": "not found" })).status(404)
+    }
+
+    pub fn status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Holds the response back for `delay`, as a slow server would.
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+type Handler = dyn Fn(&Request) -> Response + Send + Sync;
+
+/// A minimal HTTP server on 127.0.0.1 answering every request with `handler`.
+/// It stops with the test's runtime.
+pub(crate) struct StandIn {
+    pub url: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl StandIn {
+    pub async fn start(handler: impl Fn(&Request) -> Response + Send + Sync + 'static) -> Self {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let url = format!("http://127.0.0.1:{}", listener.local_addr().unwrap().port());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let handler: Arc<Handler> = Arc::new(handler);
+
+        let received = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, handler.clone(), received.clone()));
+            }
+        });
+        Self { url, requests }
+    }
+
+    /// The requests made for paths starting with `prefix`, oldest first.
+    pub fn requests(&self, prefix: &str) -> Vec<Request> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|request| request.path.starts_with(prefix))
+            .cloned()
+            .collect()
+    }
+
+    /// How many requests were made for paths starting with `prefix`.
+    pub fn hits(&self, prefix: &str) -> usize {
+        self.requests(prefix).len()
+    }
+}
+
+async fn serve(mut stream: TcpStream, handler: Arc<Handler>, requests: Arc<Mutex<Vec<Request>>>) {
+    let Some(request) = read_request(&mut stream).await else {
+        return;
+    };
+    requests.lock().unwrap().push(request.clone());
+    let response = handler(&request);
+    tokio::time::sleep(response.delay).await;
+
+    let head = format!(
+        "HTTP/1.1 {} Stand-in\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.body.len()
+    );
+    let _ = stream.write_all(head.as_bytes()).await;
+    let _ = stream.write_all(response.body.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+async fn read_request(stream: &mut TcpStream) -> Option<Request> {
+    let mut data = Vec::new();
+    let mut buf = [0u8; 4096];
+    let head_end = loop {
+        if let Some(pos) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        let n = stream.read(&mut buf).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        data.extend_from_slice(&buf[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&data[..head_end]).into_owned();
+    let mut lines = head.lines();
+    let target = lines.next()?.split_whitespace().nth(1)?.to_string();
+    let content_length = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse().ok())
+        .unwrap_or(0);
+    let mut body = data.split_off(head_end);
+    while body.len() < content_length {
+        let n = stream.read(&mut buf).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        body.extend_from_slice(&buf[..n]);
+    }
+
+    let path = target.split_once('?').map_or(target.as_str(), |(path, _)| path);
+    Some(Request {
+        path: path.to_string(),
+        body: serde_json::from_slice(&body).unwrap_or(Value::Null),
+    })
+}
+
+/// An unsigned JWT for `user` expiring in `lifetime` seconds, which is all the
+/// client reads of a Be Out token.
+pub(crate) fn token(user: &str, lifetime: u64) -> String {
+    let encode = |value: Value| URL_SAFE_NO_PAD.encode(value.to_string());
+    format!(
+        "{}.{}.",
+        encode(json!({ "alg": "none" })),
+        encode(json!({ "sub": user, "exp": super::unix_now() + lifetime }))
+    )
+}
+
+/// The `{ token, user }` body of a finished sign-in, with a day-long token.
+pub(crate) fn signed_in(email: &str) -> Value {
+    json!({
+        "token": token(email, 24 * 60 * 60),
+        "user": { "id": 7, "email": email },
+    })
+}
+
+/// A fresh directory for a test's session store.
+pub(crate) fn temp_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("beout-session-test-{}", tauri_plugin_google_auth::random_token(9)))
+}
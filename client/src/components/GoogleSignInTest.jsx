@@ -24,9 +24,6 @@ const GoogleSignInTest = () => {
             if (isTauriAvailable) {
                 console.log("=== GOOGLE SIGNIN TEST (Tauri) ===");
 
-                // For testing, use a fixed nonce that will help us debug
-                const nonce = "test_nonce_" + Date.now();
-
                 console.log("Invoking plugin:google-signin|googleSignIn");
 
                 // Call the mobile plugin command directly
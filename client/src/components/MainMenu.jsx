@@ -5,6 +5,7 @@ import { useTranslation } from "react-i18next";
 import { useTheme } from "@mui/material/styles";
 import { getIsTauriApp } from "../utils/platformDetection";
 import { isAndroid } from "../utils/platform";
+import { useGoogleAvatar } from "../hooks/useGoogleAvatar";
 import {
     AppBar,
     Toolbar,
@@ -17,12 +18,14 @@ import {
     Tabs,
     Tab,
     Divider,
+    Avatar,
 } from "@mui/material";
 import { Person as PersonIcon, Home as HomeIcon, Map as MapIcon, Event as EventIcon, Dashboard as DashboardIcon } from "@mui/icons-material";
 import LanguageSwitcher from "./LanguageSwitcher";
 
 const MainMenu = () => {
     const { user, logout } = useAuth();
+    const avatarUrl = useGoogleAvatar();
     const [anchorEl, setAnchorEl] = useState(null);
     const [isTauriApp, setIsTauriApp] = useState(false);
     const [androidStatusBarHeight, setAndroidStatusBarHeight] = useState(0);
@@ -365,7 +368,11 @@ const MainMenu = () => {
                             aria-controls="account-menu"
                             aria-haspopup="true"
                             size="large">
-                            <PersonIcon />
+                            {user && avatarUrl ? (
+                                <Avatar src={avatarUrl} alt={user.email} sx={{ width: 32, height: 32 }} />
+                            ) : (
+                                <PersonIcon />
+                            )}
                         </IconButton>
                     </Box>
 
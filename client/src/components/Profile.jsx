@@ -2,13 +2,15 @@ import React, { useState, useEffect } from "react";
 import userService from "../services/userService";
 import { useAuth } from "../context/AuthContext";
 import { useTranslation } from "react-i18next";
-import { Button, TextField, Container, Typography, Box, Alert, Grid, Paper, Divider, IconButton } from "@mui/material";
+import { Button, TextField, Container, Typography, Box, Alert, Grid, Paper, Divider, IconButton, Avatar } from "@mui/material";
 import { Edit as EditIcon, Save as SaveIcon, Cancel as CancelIcon } from "@mui/icons-material";
 import { formatDateForInput, formatDateForServer } from "../utils/dateUtils";
+import { useGoogleAvatar } from "../hooks/useGoogleAvatar";
 
 const Profile = () => {
     const { user, updateUser } = useAuth();
     const { t } = useTranslation(["profile", "common"]);
+    const avatarUrl = useGoogleAvatar();
     const [profile, setProfile] = useState({
         first_name: "",
         last_name: "",
@@ -123,9 +125,14 @@ const Profile = () => {
         <Container maxWidth="md">
             <Box sx={{ mt: 4, mb: 4 }}>
                 <Box sx={{ display: "flex", justifyContent: "space-between", alignItems: "center", mb: 2 }}>
-                    <Typography variant="h4" gutterBottom>
-                        {t("profile:title")}
-                    </Typography>
+                    <Box sx={{ display: "flex", alignItems: "center", gap: 2 }}>
+                        <Avatar src={avatarUrl ?? undefined} sx={{ width: 64, height: 64 }}>
+                            {user?.first_name?.charAt(0) || user?.email?.charAt(0)}
+                        </Avatar>
+                        <Typography variant="h4" gutterBottom>
+                            {t("profile:title")}
+                        </Typography>
+                    </Box>
                     {!isEditing ? (
                         <Button variant="contained" startIcon={<EditIcon />} onClick={handleEdit}>
                             {t("profile:edit")}
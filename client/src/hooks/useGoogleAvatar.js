@@ -0,0 +1,38 @@
+import { useState, useEffect } from "react";
+import { getCurrentUser, onAuthStateChanged } from "tauri-plugin-google-auth-api";
+import { getIsTauriApp } from "../utils/platformDetection";
+
+/**
+ * Custom hook returning the Google profile picture of the signed-in account
+ * Uses the resized copy the plugin keeps in the app cache dir, loaded through the
+ * asset protocol, so the picture is not fetched from Google on every render.
+ * Returns null in the browser and when no Google account is signed in.
+ */
+export const useGoogleAvatar = () => {
+    const [avatarUrl, setAvatarUrl] = useState(null);
+
+    useEffect(() => {
+        if (!getIsTauriApp()) {
+            return undefined;
+        }
+
+        let active = true;
+        const load = async () => {
+            try {
+                const googleUser = await getCurrentUser();
+                if (active) setAvatarUrl(googleUser?.avatarUrl ?? null);
+            } catch (error) {
+                if (active) setAvatarUrl(null);
+            }
+        };
+
+        load();
+        const unlisten = onAuthStateChanged(load);
+        return () => {
+            active = false;
+            unlisten.then((stop) => stop());
+        };
+    }, []);
+
+    return avatarUrl;
+};
@@ -51,18 +51,34 @@ export const useSplashScreen = () => {
 /**
  * Hook to automatically hide splash screen when component mounts
  * Use this in your main App component
+ *
+ * @param {number} delay - Minimum time the splash screen stays up
+ * @param {() => Promise<unknown>} [waitFor] - Keeps the splash screen up until the
+ *   returned promise settles, e.g. `restoreSession` from the google-auth plugin
  */
-export const useAutoHideSplashScreen = (delay = 1000) => {
+export const useAutoHideSplashScreen = (delay = 1000, waitFor) => {
     const { hideSplashScreen } = useSplashScreen();
 
     useEffect(() => {
+        let cancelled = false;
         // Wait a bit to ensure the app has properly rendered
-        const timer = setTimeout(() => {
-            hideSplashScreen();
-        }, delay);
+        const minimumDelay = new Promise((resolve) => setTimeout(resolve, delay));
+        const ready = waitFor
+            ? Promise.resolve()
+                  .then(waitFor)
+                  .catch((error) => console.warn('Splash screen wait failed:', error))
+            : Promise.resolve();
 
-        return () => clearTimeout(timer);
-    }, [hideSplashScreen, delay]);
+        Promise.all([minimumDelay, ready]).then(() => {
+            if (!cancelled) {
+                hideSplashScreen();
+            }
+        });
+
+        return () => {
+            cancelled = true;
+        };
+    }, [hideSplashScreen, delay, waitFor]);
 
     return { hideSplashScreen };
 };
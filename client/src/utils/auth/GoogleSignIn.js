@@ -158,7 +158,7 @@ async function signInWithGoogleTauri() {
 
     console.log('Google sign-in plugin result:', result);
 
-    if (result.success && result.idToken) {
+    if (result.idToken) {
       // Authentication successful - process the token
       const idToken = result.idToken;
 
@@ -189,10 +189,12 @@ async function signInWithGoogleTauri() {
       };
     }
   } catch (error) {
+    // Plugin errors are { code, message, details }; see the plugin's ErrorCode.
     console.error('Error during Google sign-in:', error);
     return {
       success: false,
-      error: error.toString()
+      code: error?.code,
+      error: error?.message || error.toString()
     };
   }
 }
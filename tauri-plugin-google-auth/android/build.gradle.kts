@@ -40,6 +40,11 @@ dependencies {
     implementation("com.google.android.material:material:1.11.0")
     // Google Play Services for authentication - Latest version
     implementation("com.google.android.gms:play-services-auth:21.4.0")
+    // Credential Manager, the only Android API that binds a nonce into the ID token
+    implementation("androidx.credentials:credentials:1.3.0")
+    implementation("androidx.credentials:credentials-play-services-auth:1.3.0")
+    implementation("com.google.android.libraries.identity.googleid:googleid:1.1.1")
+    implementation("org.jetbrains.kotlinx:kotlinx-coroutines-android:1.7.3")
     testImplementation("junit:junit:4.13.2")
     androidTestImplementation("androidx.test.ext:junit:1.1.5")
     androidTestImplementation("androidx.test.espresso:espresso-core:3.5.1")
@@ -7,12 +7,28 @@ import android.util.Log
 import androidx.activity.ComponentActivity
 import androidx.activity.result.ActivityResultLauncher
 import androidx.activity.result.contract.ActivityResultContracts
+import androidx.credentials.Credential
+import androidx.credentials.CredentialManager
+import androidx.credentials.CustomCredential
+import androidx.credentials.GetCredentialRequest
+import androidx.credentials.exceptions.GetCredentialCancellationException
+import androidx.credentials.exceptions.GetCredentialException
+import androidx.credentials.exceptions.NoCredentialException
 import com.google.android.gms.auth.api.signin.GoogleSignIn
 import com.google.android.gms.auth.api.signin.GoogleSignInAccount
 import com.google.android.gms.auth.api.signin.GoogleSignInClient
 import com.google.android.gms.auth.api.signin.GoogleSignInOptions
+import com.google.android.gms.auth.api.signin.GoogleSignInStatusCodes
+import com.google.android.gms.common.api.CommonStatusCodes
 import com.google.android.gms.common.api.ApiException
+import com.google.android.gms.common.api.Scope
 import com.google.android.gms.tasks.Task
+import com.google.android.libraries.identity.googleid.GetGoogleIdOption
+import com.google.android.libraries.identity.googleid.GoogleIdTokenCredential
+import com.google.android.libraries.identity.googleid.GoogleIdTokenParsingException
+import kotlinx.coroutines.CoroutineScope
+import kotlinx.coroutines.Dispatchers
+import kotlinx.coroutines.launch
 
 data class GoogleSignInResult(
     val success: Boolean,
@@ -22,7 +38,11 @@ data class GoogleSignInResult(
     val familyName: String? = null,
     val email: String? = null,
     val profilePictureUri: String? = null,
-    val error: String? = null
+    val serverAuthCode: String? = null,
+    val grantedScopes: List<String> = emptyList(),
+    val error: String? = null,
+    // Stable code understood by the Rust side, see `ErrorCode::from_native`.
+    val errorCode: String? = null
 )
 
 class GoogleAuth(private val activity: Activity) {
@@ -31,7 +51,11 @@ class GoogleAuth(private val activity: Activity) {
     }
 
     private var googleSignInClient: GoogleSignInClient
+    private val credentialManager = CredentialManager.create(activity)
     private var currentSignInCallback: ((GoogleSignInResult) -> Unit)? = null
+    // ID token obtained through Credential Manager for the sign-in in progress.
+    // The legacy SDK cannot bind a nonce, so its own ID token is never returned.
+    private var pendingIdToken: GoogleIdTokenCredential? = null
     private var signInLauncher: ActivityResultLauncher<Intent>? = null
 
     init {
@@ -57,9 +81,14 @@ class GoogleAuth(private val activity: Activity) {
                 } else {
                     Log.w(TAG, "No data in activity result")
                     currentSignInCallback?.invoke(
-                        GoogleSignInResult(success = false, error = "No data received from sign-in")
+                        GoogleSignInResult(
+                            success = false,
+                            error = "No data received from sign-in",
+                            errorCode = "cancelled"
+                        )
                     )
                     currentSignInCallback = null
+                    pendingIdToken = null
                 }
             }
             Log.d(TAG, "GoogleAuth plugin initialized successfully with ActivityResultLauncher")
@@ -86,19 +115,196 @@ class GoogleAuth(private val activity: Activity) {
         }
     }
 
-    fun signIn(callback: (GoogleSignInResult) -> Unit) {
+    /**
+     * Picks the account through Credential Manager, which binds `args.nonce` into
+     * the ID token, then signs the same account in with the Google Sign-In SDK
+     * that keeps the session, the granted scopes and the server auth code.
+     */
+    fun signIn(args: GoogleSignInArgs, callback: (GoogleSignInResult) -> Unit) {
         Log.d(TAG, "Starting Google Sign-In flow")
+        val webClientId = args.webClientId ?: getWebClientId()
+        val googleIdOption = GetGoogleIdOption.Builder()
+            .setServerClientId(webClientId)
+            .setFilterByAuthorizedAccounts(args.filterByAuthorizedAccounts ?: false)
+            .setAutoSelectEnabled(args.autoSelectEnabled ?: false)
+            .apply { args.nonce?.let { setNonce(it) } }
+            .build()
+
+        CoroutineScope(Dispatchers.Main).launch {
+            val idToken = requestIdToken(googleIdOption, callback) ?: return@launch
+            signInAccount(idToken, args, webClientId, callback)
+        }
+    }
+
+    /**
+     * Returns the ID token of the signed-in account. The Google Sign-In SDK serves
+     * it from its cache until it is about to expire; `forceRefresh` has
+     * Credential Manager issue a new one instead.
+     */
+    fun getTokens(args: GetTokensArgs, callback: (GoogleSignInResult) -> Unit) {
+        val account = GoogleSignIn.getLastSignedInAccount(activity)
+        if (account == null) {
+            callback(GoogleSignInResult(success = false, error = "No account is signed in", errorCode = "not_signed_in"))
+            return
+        }
+        val webClientId = args.webClientId ?: getWebClientId()
+
+        if (!args.forceRefresh) {
+            val gso = GoogleSignInOptions.Builder(GoogleSignInOptions.DEFAULT_SIGN_IN)
+                .requestIdToken(webClientId)
+                .requestEmail()
+                .apply { account.email?.let { setAccountName(it) } }
+                .build()
+            GoogleSignIn.getClient(activity, gso).silentSignIn().addOnCompleteListener(activity) { task ->
+                try {
+                    val refreshed = task.getResult(ApiException::class.java)
+                    callback(GoogleSignInResult(success = true, idToken = refreshed.idToken))
+                } catch (e: ApiException) {
+                    callback(
+                        GoogleSignInResult(
+                            success = false,
+                            error = "Refreshing tokens failed: ${e.message}",
+                            errorCode = errorCodeFor(e.statusCode)
+                        )
+                    )
+                }
+            }
+            return
+        }
+
+        val googleIdOption = GetGoogleIdOption.Builder()
+            .setServerClientId(webClientId)
+            .setFilterByAuthorizedAccounts(true)
+            .setAutoSelectEnabled(true)
+            .build()
+        CoroutineScope(Dispatchers.Main).launch {
+            val idToken = requestIdToken(googleIdOption, callback) ?: return@launch
+            if (idToken.id != account.email) {
+                callback(
+                    GoogleSignInResult(
+                        success = false,
+                        error = "Another account was picked while refreshing tokens",
+                        errorCode = "internal"
+                    )
+                )
+                return@launch
+            }
+            callback(GoogleSignInResult(success = true, idToken = idToken.idToken))
+        }
+    }
+
+    /** Gets a Google ID token through Credential Manager, reporting failures to `callback`. */
+    private suspend fun requestIdToken(
+        option: GetGoogleIdOption,
+        callback: (GoogleSignInResult) -> Unit
+    ): GoogleIdTokenCredential? {
+        val request = GetCredentialRequest.Builder()
+            .addCredentialOption(option)
+            .build()
+        val credential: Credential = try {
+            credentialManager.getCredential(context = activity, request = request).credential
+        } catch (e: GetCredentialException) {
+            Log.w(TAG, "Credential request failed: ${e.type}")
+            callback(
+                GoogleSignInResult(
+                    success = false,
+                    error = "Sign-in failed: ${e.message}",
+                    errorCode = errorCodeFor(e)
+                )
+            )
+            return null
+        }
+        if (credential !is CustomCredential ||
+            credential.type != GoogleIdTokenCredential.TYPE_GOOGLE_ID_TOKEN_CREDENTIAL
+        ) {
+            callback(GoogleSignInResult(success = false, error = "Unexpected credential type", errorCode = "internal"))
+            return null
+        }
+        return try {
+            GoogleIdTokenCredential.createFrom(credential.data)
+        } catch (e: GoogleIdTokenParsingException) {
+            callback(
+                GoogleSignInResult(
+                    success = false,
+                    error = "Invalid Google ID token response: ${e.message}",
+                    errorCode = "internal"
+                )
+            )
+            null
+        }
+    }
+
+    /** Signs the account of `idToken` in with the Google Sign-In SDK, silently when it can. */
+    private fun signInAccount(
+        idToken: GoogleIdTokenCredential,
+        args: GoogleSignInArgs,
+        webClientId: String,
+        callback: (GoogleSignInResult) -> Unit
+    ) {
+        val serverClientId = args.serverClientId
+        val gso = GoogleSignInOptions.Builder(GoogleSignInOptions.DEFAULT_SIGN_IN)
+            .requestIdToken(webClientId)
+            .requestEmail()
+            .setAccountName(idToken.id)
+            .apply {
+                // Offline access needs the server's client ID baked into the options.
+                if (args.requestServerAuthCode == true && serverClientId != null) {
+                    requestServerAuthCode(serverClientId, args.forceCodeForRefreshToken ?: false)
+                }
+            }
+            .build()
+        googleSignInClient = GoogleSignIn.getClient(activity, gso)
         currentSignInCallback = callback
+        pendingIdToken = idToken
 
-        val signInIntent = googleSignInClient.signInIntent
+        googleSignInClient.silentSignIn().addOnCompleteListener(activity) { task ->
+            val status = (task.exception as? ApiException)?.statusCode
+            if (task.isSuccessful || status != CommonStatusCodes.SIGN_IN_REQUIRED) {
+                handleSignInResult(task)
+                return@addOnCompleteListener
+            }
+            // First sign-in of this account in the app: ask for consent.
+            val signInIntent = googleSignInClient.signInIntent
+            // Use modern ActivityResultLauncher if available, otherwise fallback to deprecated method
+            if (signInLauncher != null) {
+                Log.d(TAG, "Using ActivityResultLauncher for sign-in")
+                signInLauncher!!.launch(signInIntent)
+            } else {
+                Log.d(TAG, "Using fallback startActivityForResult for sign-in")
+                // Fallback for non-ComponentActivity contexts
+                @Suppress("DEPRECATION")
+                activity.startActivityForResult(signInIntent, 9001)
+            }
+        }
+    }
+
+    fun requestScopes(scopes: List<String>, callback: (GoogleSignInResult) -> Unit) {
+        val account = GoogleSignIn.getLastSignedInAccount(activity)
+        if (account == null) {
+            callback(GoogleSignInResult(success = false, error = "No account is signed in", errorCode = "not_signed_in"))
+            return
+        }
+
+        val requested = scopes.map { Scope(it) }.toTypedArray()
+        if (requested.isEmpty() || GoogleSignIn.hasPermissions(account, *requested)) {
+            callback(GoogleSignInResult(success = true, grantedScopes = account.grantedScopes.map { it.scopeUri }))
+            return
+        }
 
-        // Use modern ActivityResultLauncher if available, otherwise fallback to deprecated method
+        Log.d(TAG, "Requesting additional scopes: $scopes")
+        val gso = GoogleSignInOptions.Builder(GoogleSignInOptions.DEFAULT_SIGN_IN)
+            .requestIdToken(getWebClientId())
+            .requestEmail()
+            .requestScopes(requested.first(), *requested.drop(1).toTypedArray())
+            .apply { account.email?.let { setAccountName(it) } }
+            .build()
+        googleSignInClient = GoogleSignIn.getClient(activity, gso)
+        currentSignInCallback = callback
+
+        val signInIntent = googleSignInClient.signInIntent
         if (signInLauncher != null) {
-            Log.d(TAG, "Using ActivityResultLauncher for sign-in")
             signInLauncher!!.launch(signInIntent)
         } else {
-            Log.d(TAG, "Using fallback startActivityForResult for sign-in")
-            // Fallback for non-ComponentActivity contexts
             @Suppress("DEPRECATION")
             activity.startActivityForResult(signInIntent, 9001)
         }
@@ -114,6 +320,26 @@ class GoogleAuth(private val activity: Activity) {
             }
     }
 
+    fun revokeAccess(callback: (GoogleSignInResult) -> Unit) {
+        Log.d(TAG, "Revoking Google access")
+
+        googleSignInClient.revokeAccess()
+            .addOnCompleteListener(activity) { task ->
+                if (task.isSuccessful) {
+                    callback(GoogleSignInResult(success = true))
+                } else {
+                    val statusCode = (task.exception as? ApiException)?.statusCode
+                    callback(
+                        GoogleSignInResult(
+                            success = false,
+                            error = "Revoking access failed: ${task.exception?.message}",
+                            errorCode = statusCode?.let { errorCodeFor(it) } ?: "internal"
+                        )
+                    )
+                }
+            }
+    }
+
     fun isSignedIn(): Boolean {
         val account = GoogleSignIn.getLastSignedInAccount(activity)
         return account != null
@@ -140,12 +366,14 @@ class GoogleAuth(private val activity: Activity) {
 
             val result = GoogleSignInResult(
                 success = true,
-                idToken = account.idToken,
+                idToken = pendingIdToken?.idToken ?: account.idToken,
                 displayName = account.displayName,
                 givenName = account.givenName,
                 familyName = account.familyName,
                 email = account.email,
-                profilePictureUri = account.photoUrl?.toString()
+                profilePictureUri = account.photoUrl?.toString(),
+                serverAuthCode = account.serverAuthCode,
+                grantedScopes = account.grantedScopes.map { it.scopeUri }
             )
 
             currentSignInCallback?.invoke(result)
@@ -153,11 +381,27 @@ class GoogleAuth(private val activity: Activity) {
             Log.w(TAG, "Sign-in failed: ${e.statusCode}")
             val result = GoogleSignInResult(
                 success = false,
-                error = "Sign-in failed: ${e.message}"
+                error = "Sign-in failed: ${e.message}",
+                errorCode = errorCodeFor(e.statusCode)
             )
             currentSignInCallback?.invoke(result)
         } finally {
             currentSignInCallback = null
+            pendingIdToken = null
         }
     }
+
+    private fun errorCodeFor(e: GetCredentialException): String = when (e) {
+        is GetCredentialCancellationException -> "cancelled"
+        is NoCredentialException -> "no_account"
+        else -> "internal"
+    }
+
+    private fun errorCodeFor(statusCode: Int): String = when (statusCode) {
+        GoogleSignInStatusCodes.SIGN_IN_CANCELLED -> "cancelled"
+        CommonStatusCodes.SIGN_IN_REQUIRED -> "no_account"
+        CommonStatusCodes.NETWORK_ERROR -> "network"
+        CommonStatusCodes.DEVELOPER_ERROR -> "misconfigured"
+        else -> "internal"
+    }
 }
@@ -8,6 +8,7 @@ import androidx.activity.result.contract.ActivityResultContracts
 import app.tauri.annotation.Command
 import app.tauri.annotation.InvokeArg
 import app.tauri.annotation.TauriPlugin
+import app.tauri.plugin.JSArray
 import app.tauri.plugin.JSObject
 import app.tauri.plugin.Plugin
 import app.tauri.plugin.Invoke
@@ -23,6 +24,20 @@ class GoogleSignInArgs {
     var autoSelectEnabled: Boolean? = null
     var nonce: String? = null
     var webClientId: String? = null
+    var serverClientId: String? = null
+    var requestServerAuthCode: Boolean? = null
+    var forceCodeForRefreshToken: Boolean? = null
+}
+
+@InvokeArg
+class GetTokensArgs {
+    var forceRefresh: Boolean = false
+    var webClientId: String? = null
+}
+
+@InvokeArg
+class RequestScopesArgs {
+    var scopes: Array<String> = arrayOf()
 }
 
 @TauriPlugin
@@ -59,7 +74,7 @@ class GoogleAuthPlugin(private val activity: Activity): Plugin(activity) {
     fun google_sign_in(invoke: Invoke) {
         val args = invoke.parseArgs(GoogleSignInArgs::class.java)
 
-        implementation.signIn { result ->
+        implementation.signIn(args) { result ->
             val ret = JSObject()
             ret.put("success", result.success)
             if (result.success) {
@@ -69,8 +84,45 @@ class GoogleAuthPlugin(private val activity: Activity): Plugin(activity) {
                 ret.put("familyName", result.familyName)
                 ret.put("email", result.email)
                 ret.put("profilePictureUri", result.profilePictureUri)
+                ret.put("serverAuthCode", result.serverAuthCode)
+                ret.put("grantedScopes", JSArray(result.grantedScopes))
+            } else {
+                ret.put("error", result.error)
+                ret.put("errorCode", result.errorCode)
+            }
+            invoke.resolve(ret)
+        }
+    }
+
+    @Command
+    fun get_tokens(invoke: Invoke) {
+        val args = invoke.parseArgs(GetTokensArgs::class.java)
+
+        implementation.getTokens(args) { result ->
+            val ret = JSObject()
+            ret.put("success", result.success)
+            if (result.success) {
+                ret.put("idToken", result.idToken)
+            } else {
+                ret.put("error", result.error)
+                ret.put("errorCode", result.errorCode)
+            }
+            invoke.resolve(ret)
+        }
+    }
+
+    @Command
+    fun request_scopes(invoke: Invoke) {
+        val args = invoke.parseArgs(RequestScopesArgs::class.java)
+
+        implementation.requestScopes(args.scopes.toList()) { result ->
+            val ret = JSObject()
+            ret.put("success", result.success)
+            if (result.success) {
+                ret.put("grantedScopes", JSArray(result.grantedScopes))
             } else {
                 ret.put("error", result.error)
+                ret.put("errorCode", result.errorCode)
             }
             invoke.resolve(ret)
         }
@@ -83,6 +135,20 @@ class GoogleAuthPlugin(private val activity: Activity): Plugin(activity) {
             ret.put("success", result.success)
             if (!result.success) {
                 ret.put("error", result.error)
+                ret.put("errorCode", result.errorCode)
+            }
+            invoke.resolve(ret)
+        }
+    }
+
+    @Command
+    fun revoke_access(invoke: Invoke) {
+        implementation.revokeAccess { result ->
+            val ret = JSObject()
+            ret.put("success", result.success)
+            if (!result.success) {
+                ret.put("error", result.error)
+                ret.put("errorCode", result.errorCode)
             }
             invoke.resolve(ret)
         }
@@ -1,4 +1,4 @@
-const COMMANDS: &[&str] = &["ping", "google_sign_in", "google_sign_out", "is_signed_in"];
+const COMMANDS: &[&str] = &["ping", "restore_session", "get_auth_readiness", "google_sign_in", "start_device_sign_in", "complete_device_sign_in", "cancel_sign_in", "request_scopes", "get_tokens", "google_sign_out", "list_accounts", "switch_account", "remove_account", "revoke_access", "is_signed_in", "get_current_user", "verify_id_token"];
 
 fn main() {
     tauri_plugin::Builder::new(COMMANDS)
@@ -1,7 +1,48 @@
-import { invoke } from '@tauri-apps/api/core'
+import { convertFileSrc, invoke, type InvokeArgs } from '@tauri-apps/api/core'
+import { listen, type UnlistenFn } from '@tauri-apps/api/event'
+
+/** Stable error codes reported by the plugin. */
+export type ErrorCode =
+  | 'cancelled'
+  | 'no_account'
+  | 'network'
+  | 'misconfigured'
+  | 'nonce_mismatch'
+  | 'state_mismatch'
+  | 'invalid_id_token'
+  | 'not_signed_in'
+  | 'oauth_error'
+  | 'timeout'
+  | 'policy_violation'
+  | 'unsupported_platform'
+  | 'internal';
+
+export class GoogleAuthError extends Error {
+  readonly code: ErrorCode;
+  readonly details?: unknown;
+
+  constructor(code: ErrorCode, message: string, details?: unknown) {
+    super(message);
+    this.name = 'GoogleAuthError';
+    this.code = code;
+    this.details = details ?? undefined;
+  }
+}
+
+async function call<T>(command: string, args?: InvokeArgs): Promise<T> {
+  try {
+    return await invoke<T>(`plugin:google-auth|${command}`, args);
+  } catch (e) {
+    if (e && typeof e === 'object' && 'code' in e) {
+      const { code, message, details } = e as { code: ErrorCode; message: string; details?: unknown };
+      throw new GoogleAuthError(code, message, details);
+    }
+    throw new GoogleAuthError('internal', String(e));
+  }
+}
 
 export async function ping(value: string): Promise<string | null> {
-  return await invoke<{value?: string}>('plugin:google-auth|ping', {
+  return await call<{value?: string}>('ping', {
     payload: {
       value,
     },
@@ -9,24 +50,242 @@ export async function ping(value: string): Promise<string | null> {
 }
 
 export interface GoogleSignInOptions {
+  /**
+   * Provider to sign in with: `google` (default), `apple`, `facebook` or a name from
+   * `providers` in the plugin config. Only `google` is available on mobile.
+   */
+  provider?: string;
   filterByAuthorizedAccounts?: boolean;
   autoSelectEnabled?: boolean;
   nonce?: string;
+  /** Client the server auth code is issued to. Defaults to `serverClientId` from the config. */
+  serverClientId?: string;
+  /** Also return a one-time code the backend can redeem for its own refresh token. */
+  requestServerAuthCode?: boolean;
+  /** Re-prompt for consent so the server auth code always yields a refresh token. */
+  forceCodeForRefreshToken?: boolean;
 }
 
 export interface GoogleSignInResult {
-  success: boolean;
+  /** Absent on desktop when only a server auth code was requested. */
   idToken?: string;
   displayName?: string;
   givenName?: string;
   familyName?: string;
   email?: string;
   profilePictureUri?: string;
-  error?: string;
+  serverAuthCode?: string;
+  /** Desktop only: PKCE verifier to send along with `serverAuthCode`. */
+  codeVerifier?: string;
+  /** Desktop only: redirect URI `serverAuthCode` was issued for. */
+  redirectUri?: string;
+  grantedScopes: string[];
+}
+
+/** Signs in with Google. Rejects with a {@link GoogleAuthError}. */
+export interface RestoredSession {
+  signedIn: boolean;
+  account?: AccountSummary;
+  /** Set when the session was kept but its tokens could not be refreshed, e.g. `network` offline. */
+  refreshError?: ErrorCode;
+}
+
+/**
+ * Resolves once the plugin has restored the persisted session at startup, with a
+ * definitive signed-in or signed-out answer. Keep the splash screen up until then.
+ */
+export async function restoreSession(): Promise<RestoredSession> {
+  return await call<RestoredSession>('restore_session');
+}
+
+/** Whether the startup restore has finished, without waiting for it. */
+export async function getAuthReadiness(): Promise<{ ready: boolean; session?: RestoredSession }> {
+  return await call<{ ready: boolean; session?: RestoredSession }>('get_auth_readiness');
 }
 
 export async function googleSignIn(options: GoogleSignInOptions = {}): Promise<GoogleSignInResult> {
-  return await invoke<GoogleSignInResult>('plugin:google-auth|google_sign_in', {
+  return await call<GoogleSignInResult>('google_sign_in', {
     payload: options,
   });
 }
+
+export interface DeviceSignIn {
+  userCode: string;
+  verificationUri: string;
+  verificationUriComplete?: string;
+  /** Text to render as a QR code for the user's phone. */
+  qrPayload: string;
+  /** Unix time at which the user code expires. */
+  expiresAt: number;
+  interval: number;
+  /** Resolves once the user approved the sign-in; rejects with `timeout` when the code expires. */
+  completion: Promise<GoogleSignInResult>;
+}
+
+/**
+ * Starts a sign-in the user finishes on another device (RFC 8628), for kiosks
+ * and screens without a usable browser. Desktop only.
+ */
+export async function startDeviceSignIn(options: { provider?: string } = {}): Promise<DeviceSignIn> {
+  const started = await call<Omit<DeviceSignIn, 'completion'>>('start_device_sign_in', { payload: options });
+  const completion = call<GoogleSignInResult>('complete_device_sign_in', {
+    payload: { userCode: started.userCode },
+  });
+  return { ...started, completion };
+}
+
+/**
+ * Aborts a sign-in, device sign-in or scope request in flight. The pending call rejects with
+ * code `cancelled`.
+ */
+export async function cancelSignIn(): Promise<void> {
+  await call<void>('cancel_sign_in');
+}
+
+export interface RequestScopesResult {
+  granted: string[];
+  denied: string[];
+}
+
+/**
+ * Asks the signed-in account for additional scopes. Scopes the user declined are
+ * reported in `denied` rather than rejecting.
+ */
+export async function requestScopes(scopes: string[]): Promise<RequestScopesResult> {
+  return await call<RequestScopesResult>('request_scopes', {
+    payload: { scopes },
+  });
+}
+
+export async function googleSignOut(): Promise<void> {
+  await call<void>('google_sign_out');
+}
+
+/** Revokes the app's access to the Google account and signs out. */
+export async function revokeAccess(): Promise<void> {
+  await call<void>('revoke_access');
+}
+
+export async function isSignedIn(): Promise<boolean> {
+  return await call<{isSignedIn: boolean}>('is_signed_in').then((r) => r.isSignedIn);
+}
+
+export interface CurrentUser {
+  /** Stable Google account ID (`sub`). */
+  id: string;
+  email?: string;
+  emailVerified?: boolean;
+  name?: string;
+  givenName?: string;
+  familyName?: string;
+  hd?: string;
+  locale?: string;
+  /** Remote profile picture URL. */
+  picture?: string;
+  /** Resized local copy of `picture` in the app cache dir. */
+  avatarPath?: string;
+  /**
+   * `avatarPath` as a URL the webview can load. Requires the asset protocol to
+   * allow `$APPCACHE/google-auth/avatars/*`.
+   */
+  avatarUrl?: string;
+}
+
+/** Returns the cached profile of the signed-in account, or `null` when signed out. */
+export async function getCurrentUser(): Promise<CurrentUser | null> {
+  const user = await call<CurrentUser | null>('get_current_user');
+  if (user?.avatarPath) {
+    user.avatarUrl = convertFileSrc(user.avatarPath);
+  }
+  return user;
+}
+
+export interface IdTokenClaims {
+  iss: string;
+  sub: string;
+  aud: string | string[];
+  azp?: string;
+  exp: number;
+  iat: number;
+  email?: string;
+  emailVerified?: boolean;
+  hd?: string;
+  name?: string;
+  givenName?: string;
+  familyName?: string;
+  picture?: string;
+  locale?: string;
+  nonce?: string;
+}
+
+export async function verifyIdToken(idToken: string, nonce?: string): Promise<IdTokenClaims> {
+  return await call<IdTokenClaims>('verify_id_token', {
+    payload: { idToken, nonce },
+  });
+}
+
+export interface Tokens {
+  idToken: string;
+  accessToken?: string;
+  expiresAt?: number;
+}
+
+export async function getTokens(forceRefresh = false): Promise<Tokens> {
+  return await call<Tokens>('get_tokens', {
+    payload: { forceRefresh },
+  });
+}
+
+export type AuthState =
+  | 'signed-in'
+  | 'signed-out'
+  | 'token-refreshed'
+  | 'expired'
+  | 'account-switched'
+  | 'account-removed';
+
+export interface AccountSummary {
+  /** Stable Google account ID (`sub`). */
+  id: string;
+  email?: string;
+  displayName?: string;
+  picture?: string;
+}
+
+export interface AuthStateChange {
+  state: AuthState;
+  account?: AccountSummary;
+  /** The account requests are made for after the change. */
+  activeAccount?: AccountSummary;
+}
+
+export interface AccountList {
+  accounts: AccountSummary[];
+  activeAccountId?: string;
+}
+
+/** Every account signed in on this device. Desktop keeps several; mobile only the current one. */
+export async function listAccounts(): Promise<AccountList> {
+  return await call<AccountList>('list_accounts');
+}
+
+/**
+ * Makes another signed-in account the active one. Desktop only: Android and iOS
+ * keep a single account and reject with `unsupported_platform`.
+ */
+export async function switchAccount(id: string): Promise<void> {
+  await call<void>('switch_account', { payload: { id } });
+}
+
+/**
+ * Forgets an account and its tokens without revoking its grant. Desktop only:
+ * Android and iOS reject with `unsupported_platform`; use `googleSignOut` there.
+ */
+export async function removeAccount(id: string): Promise<void> {
+  await call<void>('remove_account', { payload: { id } });
+}
+
+/** Calls `handler` whenever the user signs in or out, or the session is refreshed or expires. */
+export async function onAuthStateChanged(handler: (change: AuthStateChange) => void): Promise<UnlistenFn> {
+  return await listen<AuthStateChange>('google-auth://state-changed', (event) => handler(event.payload));
+}
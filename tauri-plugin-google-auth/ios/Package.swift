@@ -19,6 +19,7 @@ let package = Package(
     dependencies: [
         .package(url: "https://github.com/Brendonovich/swift-rs", from: "1.0.6"),
         .package(url: "https://github.com/google/GoogleSignIn-iOS", from: "7.1.0"),
+        .package(name: "Tauri", path: "../.tauri/tauri-api"),
     ],
     targets: [
         .target(
@@ -26,6 +27,7 @@ let package = Package(
             dependencies: [
                 .product(name: "SwiftRs", package: "swift-rs"),
                 .product(name: "GoogleSignIn", package: "GoogleSignIn-iOS"),
+                .product(name: "Tauri", package: "Tauri"),
             ],
             path: "Sources",
             linkerSettings: [
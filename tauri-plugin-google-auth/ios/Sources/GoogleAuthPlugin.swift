@@ -1,9 +1,26 @@
 import SwiftRs
+import Tauri
 import UIKit
 import WebKit
 import GoogleSignIn
 
-public class GoogleAuthPlugin: NSObject {
+class GoogleSignInArgs: Decodable {
+  var nonce: String?
+  var serverClientId: String?
+  var requestServerAuthCode: Bool?
+}
+
+class GetTokensArgs: Decodable {
+  var forceRefresh: Bool?
+}
+
+class RequestScopesArgs: Decodable {
+  var scopes: [String]?
+}
+
+// Commands run on Tauri's IPC queue and resolve their `Invoke` from the Google
+// Sign-In completion handlers; the SDK calls that present UI hop to the main queue.
+public class GoogleAuthPlugin: Plugin {
 
   public override init() {
     super.init()
@@ -41,57 +58,154 @@ public class GoogleAuthPlugin: NSObject {
     print("GoogleAuthPlugin: Successfully configured with client ID from plist")
   }
 
-  @objc public func google_sign_in(_ args: [String: Any]) -> [String: Any] {
+  @objc public func google_sign_in(_ invoke: Invoke) throws {
     print("GoogleAuthPlugin google_sign_in called")
+    let args = try invoke.parseArgs(GoogleSignInArgs.self)
+
+    if args.requestServerAuthCode == true,
+       let serverClientId = args.serverClientId,
+       let clientId = GIDSignIn.sharedInstance.configuration?.clientID {
+      GIDSignIn.sharedInstance.configuration = GIDConfiguration(clientID: clientId, serverClientID: serverClientId)
+    }
+
+    DispatchQueue.main.async {
+      guard let presentingViewController = self.getRootViewController() else {
+        invoke.resolve(Self.failure("Unable to get presenting view controller", code: "internal"))
+        return
+      }
 
-    guard let presentingViewController = getRootViewController() else {
-      return ["error": "Unable to get presenting view controller"]
+      // Bind the ID token to the nonce chosen by Rust
+      GIDSignIn.sharedInstance.signIn(
+        withPresenting: presentingViewController,
+        hint: nil,
+        additionalScopes: nil,
+        nonce: args.nonce
+      ) { signInResult, error in
+        if let error = error {
+          let message = "Google Sign-In failed: \(error.localizedDescription)"
+          invoke.resolve(Self.failure(message, code: Self.errorCode(for: error)))
+        } else if let user = signInResult?.user,
+                  let idToken = user.idToken?.tokenString {
+          invoke.resolve([
+            "idToken": idToken,
+            "accessToken": user.accessToken.tokenString,
+            "serverAuthCode": signInResult?.serverAuthCode,
+            "grantedScopes": user.grantedScopes ?? [],
+            "user": [
+              "id": user.userID ?? "",
+              "name": user.profile?.name ?? "",
+              "email": user.profile?.email ?? "",
+              "imageUrl": user.profile?.imageURL(withDimension: 120)?.absoluteString ?? ""
+            ]
+          ])
+        } else {
+          invoke.resolve(Self.failure("Failed to get user or ID token", code: "internal"))
+        }
+      }
     }
+  }
 
-    var result: [String: Any] = [:]
-    let semaphore = DispatchSemaphore(value: 0)
+  @objc public func get_tokens(_ invoke: Invoke) throws {
+    print("GoogleAuthPlugin get_tokens called")
+    let args = try invoke.parseArgs(GetTokensArgs.self)
 
-    // Perform async Google Sign-In
-    GIDSignIn.sharedInstance.signIn(withPresenting: presentingViewController) { signInResult, error in
+    let completion: (GIDGoogleUser?, Error?) -> Void = { user, error in
       if let error = error {
-        result = ["error": "Google Sign-In failed: \(error.localizedDescription)"]
-      } else if let user = signInResult?.user,
-                let idToken = user.idToken?.tokenString {
-        let accessToken = user.accessToken.tokenString
-
-        result = [
-          "idToken": idToken,
-          "accessToken": accessToken,
-          "user": [
-            "id": user.userID ?? "",
-            "name": user.profile?.name ?? "",
-            "email": user.profile?.email ?? "",
-            "imageUrl": user.profile?.imageURL(withDimension: 120)?.absoluteString ?? ""
-          ]
-        ]
+        let message = "Refreshing tokens failed: \(error.localizedDescription)"
+        invoke.resolve(Self.failure(message, code: Self.errorCode(for: error)))
+      } else if let user = user, let idToken = user.idToken?.tokenString {
+        invoke.resolve(["idToken": idToken, "accessToken": user.accessToken.tokenString])
       } else {
-        result = ["error": "Failed to get user or ID token"]
+        invoke.resolve(Self.failure("No account is signed in", code: "not_signed_in"))
       }
+    }
+
+    // `refreshTokensIfNeeded` only renews tokens close to expiry; restoring the
+    // previous sign-in goes back to Google with the keychain grant.
+    if args.forceRefresh != true, let user = GIDSignIn.sharedInstance.currentUser {
+      user.refreshTokensIfNeeded(completion: completion)
+    } else {
+      GIDSignIn.sharedInstance.restorePreviousSignIn(completion: completion)
+    }
+  }
+
+  @objc public func request_scopes(_ invoke: Invoke) throws {
+    print("GoogleAuthPlugin request_scopes called")
+    let args = try invoke.parseArgs(RequestScopesArgs.self)
 
-      semaphore.signal()
+    guard let user = GIDSignIn.sharedInstance.currentUser else {
+      invoke.resolve(Self.failure("No account is signed in", code: "not_signed_in"))
+      return
+    }
+    let missing = (args.scopes ?? []).filter { !(user.grantedScopes ?? []).contains($0) }
+    if missing.isEmpty {
+      invoke.resolve(["grantedScopes": user.grantedScopes ?? []])
+      return
     }
 
-    // Wait for the async operation to complete
-    semaphore.wait()
-    return result
+    DispatchQueue.main.async {
+      guard let presentingViewController = self.getRootViewController() else {
+        invoke.resolve(Self.failure("Unable to get presenting view controller", code: "internal"))
+        return
+      }
+
+      user.addScopes(missing, presenting: presentingViewController) { signInResult, error in
+        if let error = error {
+          let message = "Requesting scopes failed: \(error.localizedDescription)"
+          invoke.resolve(Self.failure(message, code: Self.errorCode(for: error)))
+        } else {
+          invoke.resolve(["grantedScopes": signInResult?.user.grantedScopes ?? []])
+        }
+      }
+    }
   }
 
-  @objc public func google_sign_out(_ args: [String: Any]) -> [String: Any] {
+  @objc public func google_sign_out(_ invoke: Invoke) {
     print("GoogleAuthPlugin google_sign_out called")
 
     GIDSignIn.sharedInstance.signOut()
-    return ["success": true]
+    invoke.resolve(["success": true])
   }
 
-  @objc public func is_signed_in(_ args: [String: Any]) -> [String: Any] {
+  @objc public func revoke_access(_ invoke: Invoke) {
+    print("GoogleAuthPlugin revoke_access called")
+
+    GIDSignIn.sharedInstance.disconnect { error in
+      if let error = error {
+        let message = "Revoking access failed: \(error.localizedDescription)"
+        invoke.resolve(Self.failure(message, code: Self.errorCode(for: error)))
+      } else {
+        invoke.resolve(["success": true])
+      }
+    }
+  }
+
+  @objc public func is_signed_in(_ invoke: Invoke) {
     print("GoogleAuthPlugin is_signed_in called")
     let isSignedIn = GIDSignIn.sharedInstance.currentUser != nil
-    return ["isSignedIn": isSignedIn]
+    invoke.resolve(["isSignedIn": isSignedIn])
+  }
+
+  // Failures are resolved in-band, like the Kotlin plugin does; `MobileBackend::run_native`
+  // turns them into errors.
+  private static func failure(_ message: String, code: String) -> JsonObject {
+    return ["error": message, "errorCode": code]
+  }
+
+  // Stable codes understood by the Rust side, see `ErrorCode::from_native`.
+  private static func errorCode(for error: Error) -> String {
+    let nsError = error as NSError
+    if nsError.domain == kGIDSignInErrorDomain {
+      switch nsError.code {
+      case GIDSignInError.canceled.rawValue: return "cancelled"
+      case GIDSignInError.hasNoAuthInKeychain.rawValue: return "no_account"
+      default: break
+      }
+    }
+    if nsError.domain == NSURLErrorDomain {
+      return "network"
+    }
+    return "internal"
   }
 
   private func getRootViewController() -> UIViewController? {
@@ -104,15 +218,7 @@ public class GoogleAuthPlugin: NSObject {
 }
 
 @_cdecl("init_plugin_google_auth")
-func initPlugin() -> UnsafeMutableRawPointer {
+func initPlugin() -> Plugin {
   print("GoogleAuthPlugin: initPlugin() called")
-  do {
-    let plugin = GoogleAuthPlugin()
-    print("GoogleAuthPlugin: Plugin created successfully")
-    return Unmanaged.passRetained(plugin).toOpaque()
-  } catch {
-    print("GoogleAuthPlugin: ERROR - Failed to create plugin: \(error)")
-    // Return a null pointer to indicate failure
-    return UnsafeMutableRawPointer(bitPattern: 0) ?? UnsafeMutableRawPointer.allocate(byteCount: 1, alignment: 1)
-  }
+  return GoogleAuthPlugin()
 }
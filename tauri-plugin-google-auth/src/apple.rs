@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{
+  config::AppleConfig,
+  id_token::{IdTokenVerifier, VerifierConfig},
+  loopback::CallbackParams,
+  models::IdTokenClaims,
+  session::SessionStore,
+  Error,
+};
+
+pub(crate) const NAME: &str = "apple";
+
+const AUTHORIZATION_ENDPOINT: &str = "https://appleid.apple.com/auth/authorize";
+const ISSUER: &str = "https://appleid.apple.com";
+const JWKS_URI: &str = "https://appleid.apple.com/auth/keys";
+const SCOPES: &[&str] = &["name", "email"];
+/// Names Apple sent on first authorization, by `sub`. Kept across sign-outs
+/// because Apple never sends them again.
+const NAMES_FILE: &str = "apple-names.bin";
+
+/// The `user` form field of Apple's first authorization. It is not signed, so
+/// only the name is taken from it; the email comes from the ID token alone.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AppleUser {
+  #[serde(default)]
+  name: Option<AppleName>,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AppleName {
+  #[serde(default)]
+  first_name: Option<String>,
+  #[serde(default)]
+  last_name: Option<String>,
+}
+
+/// Sign in with Apple in the browser.
+///
+/// Requesting `name` or `email` makes Apple deliver the result with
+/// `response_mode=form_post`, and the ID token comes straight from the
+/// authorization endpoint. The code is not redeemed here: that needs a client
+/// secret signed with the team's private key, so it is handed to the server.
+pub(crate) struct Apple {
+  config: AppleConfig,
+  verifier: IdTokenVerifier,
+}
+
+/// Verified result of an Apple authorization.
+pub(crate) struct AppleAuthorization {
+  pub id_token: String,
+  /// Claims of the ID token, completed with the name Apple sent once.
+  pub claims: IdTokenClaims,
+  pub code: Option<String>,
+}
+
+impl Apple {
+  pub fn new(http: &reqwest::Client, config: AppleConfig) -> Self {
+    let verifier = IdTokenVerifier::new(
+      http.clone(),
+      VerifierConfig {
+        jwks_uri: JWKS_URI.into(),
+        issuers: vec![ISSUER.into()],
+        audiences: vec![config.services_id.clone()],
+      },
+    );
+    Self { config, verifier }
+  }
+
+  /// Authorization URL for an attempt whose loopback listener is on `port`. The
+  /// port leads `state` so the relay page knows where to forward the form.
+  pub fn authorization_url(&self, port: u16, state: &str, nonce: Option<&str>) -> crate::Result<(Url, String)> {
+    let state = format!("{port}.{state}");
+    let mut url = Url::parse(AUTHORIZATION_ENDPOINT)?;
+    {
+      let mut query = url.query_pairs_mut();
+      query
+        .append_pair("client_id", &self.config.services_id)
+        .append_pair("redirect_uri", &self.config.redirect_uri)
+        .append_pair("response_type", "code id_token")
+        .append_pair("response_mode", "form_post")
+        .append_pair("scope", &SCOPES.join(" "))
+        .append_pair("state", &state);
+      if let Some(nonce) = nonce {
+        query.append_pair("nonce", nonce);
+      }
+    }
+    Ok((url, state))
+  }
+
+  /// Verifies an ID token Apple issued for this app.
+  pub async fn verify(&self, id_token: &str, nonce: Option<&str>) -> crate::Result<IdTokenClaims> {
+    self.verifier.verify(id_token, nonce).await
+  }
+
+  /// Verifies the posted ID token and fills in the user's name, remembering it
+  /// in `sessions` when Apple sent it.
+  pub async fn complete(
+    &self,
+    callback: CallbackParams,
+    nonce: Option<&str>,
+    sessions: &SessionStore,
+  ) -> crate::Result<AppleAuthorization> {
+    let id_token = callback
+      .id_token
+      .ok_or(Error::InvalidIdToken("authorization response has no id_token"))?;
+    let mut claims = self.verify(&id_token, nonce).await?;
+
+    let mut names: HashMap<String, AppleName> = sessions.read_sealed(NAMES_FILE).unwrap_or_default();
+    let user = callback
+      .user
+      .as_deref()
+      .and_then(|user| serde_json::from_str::<AppleUser>(user).ok());
+    if let Some(name) = user.and_then(|user| user.name) {
+      names.insert(claims.sub.clone(), name);
+      if let Err(e) = sessions.write_sealed(NAMES_FILE, &names) {
+        log::warn!("Failed to remember the Apple account name: {e}");
+      }
+    }
+
+    if let Some(name) = names.remove(&claims.sub) {
+      claims.name = match (&name.first_name, &name.last_name) {
+        (Some(first), Some(last)) => Some(format!("{first} {last}")),
+        (first, last) => first.clone().or_else(|| last.clone()),
+      };
+      claims.given_name = name.first_name;
+      claims.family_name = name.last_name;
+    }
+
+    Ok(AppleAuthorization {
+      id_token,
+      claims,
+      code: callback.code,
+    })
+  }
+}
+
+/// The scopes an Apple session was granted.
+pub(crate) fn scopes() -> Vec<String> {
+  SCOPES.iter().map(|scope| scope.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+  use crate::test_support::{claims, jwks, keys, temp_dir, Response, StandIn};
+
+  const SERVICES_ID: &str = "app.be-out.signin";
+
+  /// Apple verifying against a stand-in publishing the first test key.
+  async fn apple() -> (Apple, StandIn) {
+    let idp = StandIn::start(|request| match request.path.as_str() {
+      "/keys" => Response::json(jwks(&[&keys()[0]])),
+      _ => Response::not_found(),
+    })
+    .await;
+    let apple = Apple {
+      config: AppleConfig {
+        services_id: SERVICES_ID.into(),
+        redirect_uri: "https://be-out.app/apple/relay".into(),
+      },
+      verifier: IdTokenVerifier::new(
+        reqwest::Client::new(),
+        VerifierConfig {
+          jwks_uri: format!("{}/keys", idp.url),
+          issuers: vec![ISSUER.into()],
+          audiences: vec![SERVICES_ID.into()],
+        },
+      ),
+    };
+    (apple, idp)
+  }
+
+  fn callback(id_token: String, user: serde_json::Value) -> CallbackParams {
+    CallbackParams {
+      code: Some("apple-code".into()),
+      id_token: Some(id_token),
+      user: Some(user.to_string()),
+      ..Default::default()
+    }
+  }
+
+  #[tokio::test]
+  async fn takes_the_name_but_not_the_email_from_the_user_field() {
+    let (apple, _idp) = apple().await;
+    let sessions = SessionStore::open(&temp_dir()).unwrap();
+    // A private relay sign-in whose token carries no email.
+    let mut token_claims = claims(ISSUER, SERVICES_ID);
+    token_claims.as_object_mut().unwrap().remove("email");
+    token_claims.as_object_mut().unwrap().remove("email_verified");
+    let id_token = keys()[0].sign(&token_claims);
+    let forged = json!({
+      "name": { "firstName": "Ada", "lastName": "Lovelace" },
+      "email": "admin@be-out.app",
+    });
+
+    let authorization = apple.complete(callback(id_token, forged), None, &sessions).await.unwrap();
+    assert_eq!(authorization.claims.email, None);
+    assert_eq!(authorization.claims.email_verified, None);
+    assert_eq!(authorization.claims.name.as_deref(), Some("Ada Lovelace"));
+  }
+
+  #[tokio::test]
+  async fn keeps_the_verified_email() {
+    let (apple, _idp) = apple().await;
+    let sessions = SessionStore::open(&temp_dir()).unwrap();
+    let id_token = keys()[0].sign(&claims(ISSUER, SERVICES_ID));
+    let forged = json!({ "email": "admin@be-out.app" });
+
+    let authorization = apple.complete(callback(id_token, forged), None, &sessions).await.unwrap();
+    assert_eq!(authorization.claims.email.as_deref(), Some("ada@be-out.app"));
+  }
+}
@@ -0,0 +1,130 @@
+use std::{
+  fs,
+  io::{self, Cursor},
+  path::{Path, PathBuf},
+};
+
+use image::{imageops::FilterType, ImageFormat, ImageReader, Limits};
+use sha2::{Digest, Sha256};
+
+/// Edge length, in pixels, of cached avatars. Large enough for 2x displays of the
+/// 64 px header and profile images.
+const AVATAR_SIZE: u32 = 128;
+
+/// Largest download, in bytes, accepted as a profile picture. Google serves a few
+/// dozen kilobytes; the cap keeps a hostile `picture` URL from filling memory.
+const MAX_DOWNLOAD: u64 = 5 * 1024 * 1024;
+
+/// Largest edge, in pixels, and decoder allocation accepted for a picture, so a
+/// small file claiming huge dimensions is refused before it is decoded.
+const MAX_DIMENSION: u32 = 4096;
+const MAX_DECODE_ALLOC: u64 = 64 * 1024 * 1024;
+
+/// Profile pictures downloaded once and kept, resized, in the app cache dir.
+pub(crate) struct AvatarCache {
+  dir: PathBuf,
+  http: reqwest::Client,
+}
+
+impl AvatarCache {
+  pub fn new(dir: PathBuf, http: reqwest::Client) -> Self {
+    Self { dir, http }
+  }
+
+  /// Returns the local copy of the picture at `url`, downloading it first when it
+  /// is not cached yet. Pictures over [`MAX_DOWNLOAD`] bytes are refused.
+  pub async fn get(&self, url: &str) -> crate::Result<PathBuf> {
+    let path = self.dir.join(format!("{}.png", cache_key(url)));
+    if path.is_file() {
+      return Ok(path);
+    }
+
+    let mut response = self.http.get(url).send().await?.error_for_status()?;
+    if response.content_length().is_some_and(|length| length > MAX_DOWNLOAD) {
+      return Err(too_large().into());
+    }
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+      if (bytes.len() + chunk.len()) as u64 > MAX_DOWNLOAD {
+        return Err(too_large().into());
+      }
+      bytes.extend_from_slice(&chunk);
+    }
+    let target = path.clone();
+    tauri::async_runtime::spawn_blocking(move || resize_into(&bytes, &target)).await??;
+    Ok(path)
+  }
+}
+
+fn too_large() -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, "avatar is too large")
+}
+
+/// Decodes `bytes` within [`MAX_DIMENSION`] and [`MAX_DECODE_ALLOC`], scales the
+/// picture down to [`AVATAR_SIZE`] and writes it as PNG.
+fn resize_into(bytes: &[u8], path: &Path) -> io::Result<()> {
+  let mut limits = Limits::default();
+  limits.max_image_width = Some(MAX_DIMENSION);
+  limits.max_image_height = Some(MAX_DIMENSION);
+  limits.max_alloc = Some(MAX_DECODE_ALLOC);
+  let mut reader = ImageReader::new(Cursor::new(bytes)).with_guessed_format()?;
+  reader.limits(limits);
+  let picture = reader.decode().map_err(io::Error::other)?;
+  let avatar = picture.resize(AVATAR_SIZE, AVATAR_SIZE, FilterType::Lanczos3);
+
+  fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))?;
+  let tmp = path.with_extension("tmp");
+  avatar.save_with_format(&tmp, ImageFormat::Png).map_err(io::Error::other)?;
+  fs::rename(tmp, path)
+}
+
+/// Picture URLs change when the user updates their photo, so they make a good key.
+fn cache_key(url: &str) -> String {
+  Sha256::digest(url.as_bytes())[..16]
+    .iter()
+    .map(|byte| format!("{byte:02x}"))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use image::{ImageBuffer, Rgb};
+  use serde_json::json;
+
+  use super::*;
+  use crate::test_support::{temp_dir, Response, StandIn};
+
+  fn png(width: u32, height: u32) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    ImageBuffer::from_pixel(width, height, Rgb([200u8, 80, 40]))
+      .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+      .unwrap();
+    bytes
+  }
+
+  #[test]
+  fn scales_pictures_down_to_the_avatar_size() {
+    let path = temp_dir().join("avatar.png");
+    resize_into(&png(512, 512), &path).unwrap();
+    let avatar = image::open(&path).unwrap();
+    assert_eq!((avatar.width(), avatar.height()), (AVATAR_SIZE, AVATAR_SIZE));
+  }
+
+  #[test]
+  fn refuses_pictures_over_the_dimension_limit() {
+    let path = temp_dir().join("avatar.png");
+    assert!(resize_into(&png(MAX_DIMENSION + 1, 1), &path).is_err());
+    assert!(!path.exists());
+  }
+
+  #[tokio::test]
+  async fn refuses_downloads_over_the_size_limit() {
+    let server = StandIn::start(|_| Response::json(json!("x".repeat(MAX_DOWNLOAD as usize)))).await;
+    let dir = temp_dir();
+    let avatars = AvatarCache::new(dir.clone(), reqwest::Client::new());
+
+    let error = avatars.get(&format!("{}/photo.jpg", server.url)).await.unwrap_err();
+    assert!(matches!(error, crate::Error::Io(e) if e.kind() == io::ErrorKind::InvalidData));
+    assert!(!dir.exists());
+  }
+}
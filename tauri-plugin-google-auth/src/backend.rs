@@ -0,0 +1,130 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::models::*;
+
+/// Platform side of the plugin: runs the interactive flows and owns the session.
+///
+/// [`crate::GoogleAuth`] wraps a backend and takes care of everything that is the
+/// same on every platform: nonces, ID token verification and state change events.
+/// The plugin picks the desktop or mobile backend for the target; apps and tests
+/// can substitute their own with [`crate::Builder::backend`].
+#[async_trait]
+pub trait AuthBackend: Send + Sync + 'static {
+  async fn ping(&self, payload: PingRequest) -> crate::Result<PingResponse> {
+    Ok(PingResponse {
+      value: payload.value,
+    })
+  }
+
+  /// Runs an interactive sign-in. `request.nonce` is always set by the caller.
+  async fn sign_in(&self, request: GoogleSignInRequest) -> crate::Result<GoogleSignInResponse>;
+
+  /// Asks the signed-in account for additional scopes.
+  async fn request_scopes(&self, scopes: Vec<String>) -> crate::Result<RequestScopesResponse>;
+
+  /// Returns the current tokens, refreshing them when needed or asked to.
+  async fn tokens(&self, force_refresh: bool) -> crate::Result<FetchedTokens>;
+
+  /// Forgets the active account and its tokens.
+  async fn sign_out(&self) -> crate::Result<()>;
+
+  /// Revokes the app's grant with the identity provider and forgets the session.
+  async fn revoke(&self) -> crate::Result<()>;
+
+  async fn is_signed_in(&self) -> crate::Result<bool>;
+
+  /// Starts an OAuth 2.0 device authorization grant (RFC 8628).
+  async fn start_device_sign_in(&self, request: DeviceSignInRequest) -> crate::Result<DeviceAuthorization> {
+    let _ = request;
+    Err(crate::Error::UnsupportedPlatform)
+  }
+
+  /// Polls until the user approves `authorization` on another device, then keeps
+  /// the resulting session like [`AuthBackend::sign_in`] does.
+  async fn complete_device_sign_in(&self, authorization: DeviceAuthorization) -> crate::Result<GoogleSignInResponse> {
+    let _ = authorization;
+    Err(crate::Error::UnsupportedPlatform)
+  }
+
+  /// Verifies an ID token that `provider`, other than Google, issued for this app.
+  /// Backends that sign in with other providers must implement this; the plugin
+  /// refuses tokens it cannot verify.
+  async fn verify_id_token(&self, provider: &str, id_token: &str, nonce: Option<&str>) -> crate::Result<IdTokenClaims> {
+    let _ = (provider, id_token, nonce);
+    Err(crate::Error::UnsupportedPlatform)
+  }
+
+  /// Verified ID token claims of the local session, when the backend keeps one.
+  fn claims(&self) -> Option<IdTokenClaims> {
+    None
+  }
+
+  /// The account of the local session, when the backend keeps one.
+  fn account(&self) -> Option<AccountSummary> {
+    self.claims().as_ref().map(AccountSummary::from)
+  }
+
+  /// Every account with a stored session, the active one included.
+  fn accounts(&self) -> Vec<AccountSummary> {
+    self.account().into_iter().collect()
+  }
+
+  /// Makes another stored account the active one.
+  async fn switch_account(&self, id: &str) -> crate::Result<()> {
+    let _ = id;
+    Err(crate::Error::UnsupportedPlatform)
+  }
+
+  /// Forgets a stored account and its tokens without revoking its grant.
+  async fn remove_account(&self, id: &str) -> crate::Result<()> {
+    let _ = id;
+    Err(crate::Error::UnsupportedPlatform)
+  }
+
+  /// Whether ID tokens returned by [`AuthBackend::sign_in`] carry the request nonce.
+  fn binds_nonce(&self) -> bool {
+    true
+  }
+}
+
+/// Tokens returned by [`AuthBackend::tokens`].
+#[derive(Debug, Clone)]
+pub struct FetchedTokens {
+  pub tokens: GetTokensResponse,
+  /// Whether new tokens were obtained rather than served from the session.
+  pub refreshed: bool,
+}
+
+/// A pending device authorization returned by [`AuthBackend::start_device_sign_in`].
+#[derive(Debug, Clone)]
+pub struct DeviceAuthorization {
+  /// Name of the identity provider, as in [`GoogleSignInRequest::provider`].
+  pub provider: String,
+  pub device_code: String,
+  pub user_code: String,
+  pub verification_uri: String,
+  pub verification_uri_complete: Option<String>,
+  /// Unix time at which the device code expires.
+  pub expires_at: u64,
+  /// Minimum delay between polls.
+  pub interval: Duration,
+}
+
+impl DeviceAuthorization {
+  /// What the user sees: everything but the device code.
+  pub fn response(&self) -> DeviceSignInResponse {
+    DeviceSignInResponse {
+      user_code: self.user_code.clone(),
+      verification_uri: self.verification_uri.clone(),
+      verification_uri_complete: self.verification_uri_complete.clone(),
+      qr_payload: self
+        .verification_uri_complete
+        .clone()
+        .unwrap_or_else(|| self.verification_uri.clone()),
+      expires_at: self.expires_at,
+      interval: self.interval.as_secs(),
+    }
+  }
+}
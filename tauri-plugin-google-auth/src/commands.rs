@@ -9,7 +9,21 @@ pub(crate) async fn ping<R: Runtime>(
     app: AppHandle<R>,
     payload: PingRequest,
 ) -> Result<PingResponse> {
-    app.google_auth().ping(payload)
+    app.google_auth().ping(payload).await
+}
+
+#[command]
+pub(crate) async fn restore_session<R: Runtime>(
+    app: AppHandle<R>,
+) -> Result<RestoredSession> {
+    Ok(app.google_auth().restore_session().await)
+}
+
+#[command]
+pub(crate) async fn get_auth_readiness<R: Runtime>(
+    app: AppHandle<R>,
+) -> Result<AuthReadiness> {
+    Ok(app.google_auth().readiness())
 }
 
 #[command]
@@ -17,19 +31,111 @@ pub(crate) async fn google_sign_in<R: Runtime>(
     app: AppHandle<R>,
     payload: GoogleSignInRequest,
 ) -> Result<GoogleSignInResponse> {
-    app.google_auth().google_sign_in(payload)
+    app.google_auth().google_sign_in(payload).await
+}
+
+#[command]
+pub(crate) async fn start_device_sign_in<R: Runtime>(
+    app: AppHandle<R>,
+    payload: Option<DeviceSignInRequest>,
+) -> Result<DeviceSignInResponse> {
+    app.google_auth()
+        .start_device_sign_in(payload.unwrap_or_default())
+        .await
+}
+
+#[command]
+pub(crate) async fn complete_device_sign_in<R: Runtime>(
+    app: AppHandle<R>,
+    payload: CompleteDeviceSignInRequest,
+) -> Result<GoogleSignInResponse> {
+    app.google_auth()
+        .complete_device_sign_in(&payload.user_code)
+        .await
+}
+
+#[command]
+pub(crate) async fn cancel_sign_in<R: Runtime>(
+    app: AppHandle<R>,
+) -> Result<()> {
+    app.google_auth().cancel_sign_in();
+    Ok(())
+}
+
+#[command]
+pub(crate) async fn request_scopes<R: Runtime>(
+    app: AppHandle<R>,
+    payload: RequestScopesRequest,
+) -> Result<RequestScopesResponse> {
+    app.google_auth().request_scopes(payload.scopes).await
+}
+
+#[command]
+pub(crate) async fn get_tokens<R: Runtime>(
+    app: AppHandle<R>,
+    payload: Option<GetTokensRequest>,
+) -> Result<GetTokensResponse> {
+    let force_refresh = payload.and_then(|p| p.force_refresh).unwrap_or(false);
+    app.google_auth().tokens(force_refresh).await
 }
 
 #[command]
 pub(crate) async fn google_sign_out<R: Runtime>(
     app: AppHandle<R>,
-) -> Result<GoogleSignOutResponse> {
-    app.google_auth().google_sign_out()
+) -> Result<()> {
+    app.google_auth().google_sign_out().await
+}
+
+#[command]
+pub(crate) async fn list_accounts<R: Runtime>(
+    app: AppHandle<R>,
+) -> Result<ListAccountsResponse> {
+    Ok(app.google_auth().list_accounts())
+}
+
+#[command]
+pub(crate) async fn switch_account<R: Runtime>(
+    app: AppHandle<R>,
+    payload: AccountRequest,
+) -> Result<()> {
+    app.google_auth().switch_account(&payload.id).await
+}
+
+#[command]
+pub(crate) async fn remove_account<R: Runtime>(
+    app: AppHandle<R>,
+    payload: AccountRequest,
+) -> Result<()> {
+    app.google_auth().remove_account(&payload.id).await
+}
+
+#[command]
+pub(crate) async fn revoke_access<R: Runtime>(
+    app: AppHandle<R>,
+) -> Result<()> {
+    app.google_auth().revoke_access().await
 }
 
 #[command]
 pub(crate) async fn is_signed_in<R: Runtime>(
     app: AppHandle<R>,
 ) -> Result<IsSignedInResponse> {
-    app.google_auth().is_signed_in()
+    app.google_auth().is_signed_in().await
+}
+
+#[command]
+pub(crate) async fn get_current_user<R: Runtime>(
+    app: AppHandle<R>,
+) -> Result<Option<CurrentUser>> {
+    app.google_auth().current_user().await
+}
+
+#[command]
+pub(crate) async fn verify_id_token<R: Runtime>(
+    app: AppHandle<R>,
+    payload: VerifyIdTokenRequest,
+) -> Result<IdTokenClaims> {
+    app.google_auth()
+        .verify_id_token(&payload.id_token, payload.nonce.as_deref())
+        .await
 }
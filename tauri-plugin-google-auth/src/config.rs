@@ -0,0 +1,240 @@
+use std::{collections::HashMap, time::Duration};
+
+use serde::Deserialize;
+
+use crate::{Error, Options};
+
+const DEFAULT_SCOPES: &[&str] = &["openid", "email", "profile"];
+const DEFAULT_SIGN_IN_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Plugin configuration, read from `plugins.google-auth` in `tauri.conf.json`.
+///
+/// ```json
+/// "plugins": {
+///   "google-auth": {
+///     "webClientId": "….apps.googleusercontent.com",
+///     "desktopClientId": "….apps.googleusercontent.com",
+///     "desktopClientSecret": "GOCSPX-…",
+///     "deviceClientId": "….apps.googleusercontent.com",
+///     "scopes": ["openid", "email", "profile"],
+///     "hostedDomain": "be-out.app",
+///     "redirectPorts": { "start": 49152, "end": 49200 },
+///     "signInTimeoutSecs": 300,
+///     "providers": {
+///       "partner-sso": { "issuer": "https://sso.example.org/realms/events", "clientId": "be-out" }
+///     },
+///     "apple": { "servicesId": "app.be-out.signin", "redirectUri": "https://be-out.app/auth/apple/relay" },
+///     "facebook": { "appId": "1234567890" },
+///     "policy": {
+///       "allowedHostedDomains": ["be-out.app"],
+///       "requireVerifiedEmail": true,
+///       "deniedEmails": ["*+test@be-out.app"]
+///     }
+///   }
+/// }
+/// ```
+///
+/// Values set on the [`crate::Builder`] take precedence.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Config {
+  /// Web client ID. Used as ID token audience on Android and as desktop client
+  /// when no `desktopClientId` is set.
+  pub web_client_id: Option<String>,
+  pub ios_client_id: Option<String>,
+  pub android_client_id: Option<String>,
+  /// OAuth client of the "Desktop app" type used by the loopback flow.
+  pub desktop_client_id: Option<String>,
+  pub desktop_client_secret: Option<String>,
+  /// OAuth client of the "TVs and Limited Input devices" type used by the device
+  /// flow. Google rejects the device flow for desktop clients.
+  pub device_client_id: Option<String>,
+  pub device_client_secret: Option<String>,
+  /// Client ID of the Be Out server, for server auth codes.
+  pub server_client_id: Option<String>,
+  /// Scopes requested at sign-in. Defaults to `openid email profile`.
+  #[serde(default)]
+  pub scopes: Vec<String>,
+  /// Google Workspace domain to restrict the account picker to.
+  pub hosted_domain: Option<String>,
+  /// Ports the desktop loopback listener may bind to. Defaults to any free port.
+  pub redirect_ports: Option<PortRange>,
+  /// Seconds an interactive sign-in may take before it fails with `timeout`.
+  /// Defaults to 300.
+  pub sign_in_timeout_secs: Option<u64>,
+  /// Further OpenID Connect providers for the desktop flow, by name. `google` is
+  /// always available and configured by the settings above.
+  #[serde(default)]
+  pub providers: HashMap<String, ProviderConfig>,
+  /// Enables Sign in with Apple on desktop as the `apple` provider.
+  pub apple: Option<AppleConfig>,
+  /// Enables Facebook Login on desktop as the `facebook` provider.
+  pub facebook: Option<FacebookConfig>,
+  /// Restricts which accounts may sign in. Enforced in Rust on every platform.
+  #[serde(default)]
+  pub policy: AccountPolicy,
+}
+
+/// Which accounts may sign in. Every rule that is set must pass.
+///
+/// Email patterns are matched case-insensitively and may use `*` for any run
+/// of characters, e.g. `*@be-out.app`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct AccountPolicy {
+  /// Google Workspace domains (`hd` claim) accounts must belong to. Consumer
+  /// and non-Google accounts have none and are refused when this is set.
+  #[serde(default)]
+  pub allowed_hosted_domains: Vec<String>,
+  /// Refuse accounts whose email the provider has not verified.
+  #[serde(default)]
+  pub require_verified_email: bool,
+  /// Emails that may sign in. Empty allows every email.
+  #[serde(default)]
+  pub allowed_emails: Vec<String>,
+  /// Emails that may not sign in, even when allowed above.
+  #[serde(default)]
+  pub denied_emails: Vec<String>,
+}
+
+/// Sign in with Apple through the browser.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct AppleConfig {
+  /// Services ID the web flow is registered under, used as ID token audience.
+  pub services_id: String,
+  /// HTTPS return URL registered for the Services ID. Apple does not accept
+  /// loopback redirects, so this page has to forward the posted form to
+  /// `http://127.0.0.1:{port}/`, where `port` is the part of `state` before the
+  /// first `.`.
+  pub redirect_uri: String,
+}
+
+/// Facebook Login through the browser, using its OpenID Connect flow with PKCE.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct FacebookConfig {
+  pub app_id: String,
+  /// Graph API version of the dialog and token endpoints. Defaults to `v19.0`.
+  pub graph_api_version: Option<String>,
+}
+
+/// An OpenID Connect provider whose endpoints and keys are discovered from
+/// `{issuer}/.well-known/openid-configuration`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ProviderConfig {
+  pub issuer: String,
+  pub client_id: String,
+  pub client_secret: Option<String>,
+  /// Defaults to whichever of `openid email profile` the provider supports.
+  #[serde(default)]
+  pub scopes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct PortRange {
+  pub start: u16,
+  pub end: u16,
+}
+
+impl Config {
+  /// Every client ID an ID token issued to this app may be addressed to.
+  fn client_ids(&self) -> impl Iterator<Item = &String> {
+    [
+      &self.web_client_id,
+      &self.ios_client_id,
+      &self.android_client_id,
+      &self.desktop_client_id,
+      &self.device_client_id,
+      &self.server_client_id,
+    ]
+    .into_iter()
+    .flatten()
+  }
+}
+
+impl Options {
+  /// Fills everything the builder left unset from `config` and checks that the
+  /// result is usable on the current platform.
+  pub(crate) fn resolve(mut self, config: Config) -> crate::Result<Self> {
+    if self.client.client_id.is_none() {
+      self.client.client_id = config
+        .desktop_client_id
+        .clone()
+        .or_else(|| config.web_client_id.clone());
+      self.client.client_secret = self
+        .client
+        .client_secret
+        .or_else(|| config.desktop_client_secret.clone());
+    }
+    if self.client.scopes.is_empty() {
+      self.client.scopes = if config.scopes.is_empty() {
+        DEFAULT_SCOPES.iter().map(|s| s.to_string()).collect()
+      } else {
+        config.scopes.clone()
+      };
+    }
+    if !self.client.scopes.iter().any(|scope| scope == "openid") {
+      return Err(Error::InvalidConfig("`scopes` must include `openid`".into()));
+    }
+    if self.client.hosted_domain.is_none() {
+      self.client.hosted_domain = config.hosted_domain.clone();
+    }
+
+    if self.redirect_ports.is_none() {
+      if let Some(PortRange { start, end }) = config.redirect_ports {
+        if start == 0 || start > end {
+          return Err(Error::InvalidConfig(format!(
+            "`redirectPorts` {start}-{end} is not a valid port range"
+          )));
+        }
+        self.redirect_ports = Some(start..=end);
+      }
+    }
+
+    if self.sign_in_timeout.is_none() {
+      self.sign_in_timeout = Some(match config.sign_in_timeout_secs {
+        Some(0) => return Err(Error::InvalidConfig("`signInTimeoutSecs` must be positive".into())),
+        Some(secs) => Duration::from_secs(secs),
+        None => DEFAULT_SIGN_IN_TIMEOUT,
+      });
+    }
+
+    for reserved in ["google", "apple", "facebook"] {
+      if config.providers.contains_key(reserved) {
+        return Err(Error::InvalidConfig(format!("`providers.{reserved}` is a built-in provider")));
+      }
+    }
+    let policy = &config.policy;
+    if policy.allowed_emails.iter().chain(&policy.denied_emails).any(|pattern| pattern.trim().is_empty()) {
+      return Err(Error::InvalidConfig("`policy` email patterns must not be empty".into()));
+    }
+    if let Some(apple) = &config.apple {
+      if !apple.redirect_uri.starts_with("https://") {
+        return Err(Error::InvalidConfig("`apple.redirectUri` must be an https URL".into()));
+      }
+    }
+    for (name, provider) in &config.providers {
+      if !provider.scopes.is_empty() && !provider.scopes.iter().any(|scope| scope == "openid") {
+        return Err(Error::InvalidConfig(format!("`providers.{name}.scopes` must include `openid`")));
+      }
+    }
+
+    if self.verifier.audiences.is_empty() {
+      for client_id in config.client_ids().chain(&self.client.client_id) {
+        if !self.verifier.audiences.contains(client_id) {
+          self.verifier.audiences.push(client_id.clone());
+        }
+      }
+    }
+
+    #[cfg(target_os = "android")]
+    if config.web_client_id.is_none() && config.server_client_id.is_none() {
+      log::warn!("google-auth: no `webClientId` configured, falling back to `default_web_client_id` resource");
+    }
+
+    self.config = config;
+    Ok(self)
+  }
+}
@@ -1,52 +1,832 @@
-use tauri::{plugin::PluginApi, AppHandle, Runtime};
+use std::{path::Path, time::Instant};
 
-use crate::models::*;
+use async_trait::async_trait;
+use tauri::{plugin::PluginApi, AppHandle, Manager, Runtime};
+use tokio::sync::Mutex;
+
+use url::Url;
+
+use crate::{
+  apple::{self, Apple},
+  backend::{AuthBackend, DeviceAuthorization, FetchedTokens},
+  device, facebook,
+  loopback::{CallbackParams, LoopbackServer},
+  models::*,
+  oauth::{self, split_scopes, unix_now, AuthorizationParams, AuthorizationRequest, ClientConfig},
+  oidc::{self, Provider, Providers},
+  pkce::{self, Pkce},
+  session::{SessionStore, StoredSession},
+  Config, Error, Options,
+};
+
+/// Access tokens expiring within this many seconds are refreshed before use.
+const REFRESH_MARGIN: u64 = 60;
 
 pub fn init<R: Runtime>(
   app: &AppHandle<R>,
-  _api: PluginApi<R, ()>,
-) -> crate::Result<GoogleAuth<R>> {
-  Ok(GoogleAuth(app.clone()))
+  _api: PluginApi<R, Option<Config>>,
+  options: Options,
+) -> crate::Result<DesktopBackend> {
+  let session_dir = match &options.session_dir {
+    Some(dir) => dir.clone(),
+    None => app.path().app_data_dir()?.join("google-auth"),
+  };
+  DesktopBackend::new(options, &session_dir)
+}
+
+/// Browser based OpenID Connect sign-in with an encrypted session in the app
+/// data dir.
+pub(crate) struct DesktopBackend {
+  http: reqwest::Client,
+  providers: Providers,
+  apple: Option<Apple>,
+  sessions: SessionStore,
+  /// Serializes refreshes so concurrent callers share one token exchange.
+  refresh_lock: Mutex<()>,
+  options: Options,
 }
 
-/// Access to the google-auth APIs.
-pub struct GoogleAuth<R: Runtime>(AppHandle<R>);
+#[async_trait]
+impl AuthBackend for DesktopBackend {
+  /// Runs the OAuth 2.0 authorization code flow with PKCE in the system browser,
+  /// receiving the redirect on an ephemeral loopback listener, against the
+  /// provider named in the request.
+  ///
+  /// With `request_server_auth_code` the authorization code is not redeemed here
+  /// but returned, together with its PKCE verifier and redirect URI, for the
+  /// backend to exchange. No local session is created in that case.
+  async fn sign_in(&self, payload: GoogleSignInRequest) -> crate::Result<GoogleSignInResponse> {
+    let provider_name = payload.provider.clone().unwrap_or_else(|| oidc::GOOGLE.into());
+    if provider_name == apple::NAME {
+      return self.sign_in_with_apple(payload).await;
+    }
+    let provider = self.providers.get(&provider_name).await?;
+    let server_auth_code = payload.request_server_auth_code.unwrap_or(false);
+    let mut client = provider.client.clone();
+    if server_auth_code && provider_name == oidc::GOOGLE {
+      if let Some(server_client_id) = payload
+        .server_client_id
+        .clone()
+        .or_else(|| self.options.config.server_client_id.clone())
+      {
+        client.client_id = Some(server_client_id);
+        client.client_secret = None;
+      }
+    }
+
+    let authorization = self
+      .authorize(
+        &client,
+        &AuthorizationParams {
+          nonce: payload.nonce.as_deref(),
+          force_consent: payload.force_code_for_refresh_token.unwrap_or(false),
+          ..Default::default()
+        },
+      )
+      .await?;
+
+    if server_auth_code {
+      return Ok(GoogleSignInResponse {
+        id_token: None,
+        display_name: None,
+        given_name: None,
+        family_name: None,
+        profile_picture_uri: None,
+        email: None,
+        granted_scopes: split_scopes(authorization.scope.as_deref()).unwrap_or(client.scopes),
+        server_auth_code: Some(authorization.code),
+        code_verifier: Some(authorization.code_verifier),
+        redirect_uri: Some(authorization.redirect_uri),
+      });
+    }
+
+    let tokens = authorization.exchange(&self.http, &client).await?;
+    self
+      .keep_session(&provider, provider_name, false, tokens, payload.nonce.as_deref(), client.scopes)
+      .await
+  }
+
+  /// Asks the signed-in account for additional scopes and merges whatever the
+  /// user granted into the stored session.
+  async fn request_scopes(&self, scopes: Vec<String>) -> crate::Result<RequestScopesResponse> {
+    let session = self.sessions.get().ok_or(Error::NotSignedIn)?;
+    if scopes.is_empty() {
+      return Ok(RequestScopesResponse::default());
+    }
+    let provider = self.providers.get(&session.provider).await?;
+    let client = ClientConfig {
+      scopes: scopes.clone(),
+      ..provider.client.clone()
+    };
+    let nonce = pkce::random_token(32);
+    let authorization = self
+      .authorize(
+        &client,
+        &AuthorizationParams {
+          nonce: Some(&nonce),
+          login_hint: Some(session.claims.email.as_deref().unwrap_or(&session.claims.sub)),
+          include_granted_scopes: true,
+          ..Default::default()
+        },
+      )
+      .await?;
+    let tokens = authorization.exchange(&self.http, &client).await?;
+
+    let _guard = self.refresh_lock.lock().await;
+    // Look the account up again: it may no longer be the active one.
+    let mut updated = self.sessions.account(&session.claims.sub).ok_or(Error::NotSignedIn)?;
+    if let Some(id_token) = tokens.id_token {
+      let claims = provider.verifier.verify(&id_token, Some(&nonce)).await?;
+      if claims.sub != updated.claims.sub {
+        return Err(Error::OAuth {
+          error: "account_mismatch".into(),
+          description: Some("consent was given by a different account than the signed-in one".into()),
+        });
+      }
+      updated.claims = claims;
+      updated.id_token = id_token;
+    }
+    let granted_now = split_scopes(tokens.scope.as_deref()).unwrap_or_default();
+    for scope in &granted_now {
+      if !updated.scopes.contains(scope) {
+        updated.scopes.push(scope.clone());
+      }
+    }
+    updated.access_token = Some(tokens.access_token);
+    updated.expires_at = tokens.expires_in.map(|secs| unix_now() + secs);
+    if let Some(refresh_token) = tokens.refresh_token {
+      updated.refresh_token = Some(refresh_token);
+    }
+
+    let (granted, denied) = scopes
+      .into_iter()
+      .partition(|scope| oauth::scope_granted(&updated.scopes, scope));
+    self.sessions.save(updated)?;
+    Ok(RequestScopesResponse { granted, denied })
+  }
+
+  /// Returns the current ID and access tokens, refreshing them first when they are
+  /// about to expire or `force_refresh` is set. A Google session without a
+  /// refresh token is forgotten once its access token has expired.
+  async fn tokens(&self, force_refresh: bool) -> crate::Result<FetchedTokens> {
+    let seen = self.sessions.get().ok_or(Error::NotSignedIn)?;
+    // Apple and Facebook issue no refresh tokens; their tokens are used until the
+    // user signs in again.
+    if seen.refresh_token.is_none() && seen.provider != oidc::GOOGLE {
+      return Ok(cached(seen));
+    }
+    if !force_refresh && !seen.expires_within(REFRESH_MARGIN) {
+      return Ok(cached(seen));
+    }
+
+    let _guard = self.refresh_lock.lock().await;
+    let session = self.sessions.get().ok_or(Error::NotSignedIn)?;
+    // Another caller refreshed while we were waiting for the lock.
+    if session.access_token != seen.access_token && !session.expires_within(REFRESH_MARGIN) {
+      return Ok(cached(session));
+    }
+
+    let Some(refresh_token) = session.refresh_token.clone() else {
+      if !session.expires_within(0) {
+        return Ok(cached(session));
+      }
+      // Nothing can renew it, so keep it from being reported as signed in.
+      self.sessions.remove(&session.claims.sub)?;
+      return Err(Error::NotSignedIn);
+    };
+    let provider = self.providers.get(&session.provider).await?;
+    let client = self.client(&session.provider, &provider, session.device);
+    let tokens = match oauth::refresh(&self.http, &client, &refresh_token).await {
+      Ok(tokens) => tokens,
+      Err(Error::OAuth { error, description }) if error == "invalid_grant" => {
+        // The grant was revoked or has expired; the stored session is useless now.
+        self.sessions.remove(&session.claims.sub)?;
+        return Err(Error::OAuth { error, description });
+      }
+      Err(e) => return Err(e),
+    };
 
-impl<R: Runtime> GoogleAuth<R> {
-  pub fn ping(&self, payload: PingRequest) -> crate::Result<PingResponse> {
-    Ok(PingResponse {
-      value: payload.value,
+    let mut refreshed = session;
+    if let Some(id_token) = tokens.id_token {
+      refreshed.claims = provider.verifier.verify(&id_token, None).await?;
+      refreshed.id_token = id_token;
+    }
+    refreshed.access_token = Some(tokens.access_token);
+    refreshed.expires_at = tokens.expires_in.map(|secs| unix_now() + secs);
+    if let Some(refresh_token) = tokens.refresh_token {
+      refreshed.refresh_token = Some(refresh_token);
+    }
+    if let Some(scopes) = split_scopes(tokens.scope.as_deref()) {
+      refreshed.scopes = scopes;
+    }
+    self.sessions.save(refreshed.clone())?;
+
+    Ok(FetchedTokens {
+      tokens: tokens_response(refreshed),
+      refreshed: true,
     })
   }
 
-  pub fn google_sign_in(&self, _payload: GoogleSignInRequest) -> crate::Result<GoogleSignInResponse> {
-    // Desktop implementation would use web-based OAuth flow
-    // For now, return a placeholder response
+  /// Forgets the active account and its tokens. Other accounts stay stored.
+  async fn sign_out(&self) -> crate::Result<()> {
+    self.sessions.clear()
+  }
+
+  /// Revokes the refresh token, or the access token when there is none, then
+  /// clears the session. The session is kept when revocation fails so the user
+  /// can retry.
+  async fn revoke(&self) -> crate::Result<()> {
+    let session = self.sessions.get().ok_or(Error::NotSignedIn)?;
+    match session.provider.as_str() {
+      apple::NAME => {
+        return Err(Error::OAuth {
+          error: "unsupported_token_type".into(),
+          description: Some("Apple grants can only be revoked by the server, which holds the client secret".into()),
+        })
+      }
+      facebook::NAME => {
+        let config = self
+          .options
+          .config
+          .facebook
+          .as_ref()
+          .ok_or(Error::MissingConfig("facebook"))?;
+        let access_token = session.access_token.as_deref().ok_or(Error::NotSignedIn)?;
+        facebook::revoke(&self.http, config, access_token).await?;
+        self.sessions.remove(&session.claims.sub)?;
+        return Ok(());
+      }
+      _ => {}
+    }
+    let token = session
+      .refresh_token
+      .as_deref()
+      .or(session.access_token.as_deref())
+      .ok_or(Error::NotSignedIn)?;
+    let provider = self.providers.get(&session.provider).await?;
+    let client = self.client(&session.provider, &provider, session.device);
+    oauth::revoke(&self.http, &client, token).await?;
+    self.sessions.remove(&session.claims.sub)?;
+    Ok(())
+  }
+
+  async fn is_signed_in(&self) -> crate::Result<bool> {
+    Ok(self.sessions.get().is_some())
+  }
+
+  async fn start_device_sign_in(&self, request: DeviceSignInRequest) -> crate::Result<DeviceAuthorization> {
+    let provider_name = request.provider.unwrap_or_else(|| oidc::GOOGLE.into());
+    let provider = self.providers.get(&provider_name).await?;
+    let client = self.client(&provider_name, &provider, true);
+    device::start(&self.http, &client, &provider_name).await
+  }
+
+  async fn complete_device_sign_in(&self, authorization: DeviceAuthorization) -> crate::Result<GoogleSignInResponse> {
+    let provider = self.providers.get(&authorization.provider).await?;
+    let client = self.client(&authorization.provider, &provider, true);
+    let tokens = device::poll(&self.http, &client, &authorization).await?;
+    self
+      .keep_session(&provider, authorization.provider, true, tokens, None, client.scopes)
+      .await
+  }
+
+  async fn verify_id_token(&self, provider: &str, id_token: &str, nonce: Option<&str>) -> crate::Result<IdTokenClaims> {
+    if provider == apple::NAME {
+      let apple = self.apple.as_ref().ok_or(Error::MissingConfig("apple"))?;
+      return apple.verify(id_token, nonce).await;
+    }
+    self.providers.get(provider).await?.verifier.verify(id_token, nonce).await
+  }
+
+  fn claims(&self) -> Option<IdTokenClaims> {
+    self.sessions.get().map(|session| session.claims)
+  }
+
+  fn accounts(&self) -> Vec<AccountSummary> {
+    self
+      .sessions
+      .list()
+      .iter()
+      .map(|session| AccountSummary::from(&session.claims))
+      .collect()
+  }
+
+  async fn switch_account(&self, id: &str) -> crate::Result<()> {
+    match self.sessions.activate(id)? {
+      true => Ok(()),
+      false => Err(Error::UnknownAccount(id.to_string())),
+    }
+  }
+
+  async fn remove_account(&self, id: &str) -> crate::Result<()> {
+    match self.sessions.remove(id)? {
+      true => Ok(()),
+      false => Err(Error::UnknownAccount(id.to_string())),
+    }
+  }
+}
+
+impl DesktopBackend {
+  fn new(options: Options, session_dir: &Path) -> crate::Result<Self> {
+    if options.client.client_id.is_none() {
+      return Err(Error::MissingConfig("desktopClientId"));
+    }
+    let http = reqwest::Client::new();
+    let mut providers = Providers::new(
+      http.clone(),
+      options.client.clone(),
+      options.verifier.clone(),
+      options.config.providers.clone(),
+    );
+    if let Some(config) = &options.config.facebook {
+      providers = providers.preset(facebook::NAME, facebook::provider(&http, config));
+    }
+    Ok(Self {
+      providers,
+      apple: options.config.apple.clone().map(|config| Apple::new(&http, config)),
+      http,
+      sessions: SessionStore::open(session_dir)?,
+      refresh_lock: Mutex::new(()),
+      options,
+    })
+  }
+
+  /// The client of `provider` to use for browser or, with `device`, device flow
+  /// sign-ins. Google needs a separate client type for the latter.
+  fn client(&self, name: &str, provider: &Provider, device: bool) -> ClientConfig {
+    let mut client = provider.client.clone();
+    if device && name == oidc::GOOGLE {
+      if let Some(client_id) = self.options.config.device_client_id.clone() {
+        client.client_id = Some(client_id);
+        client.client_secret = self.options.config.device_client_secret.clone();
+      }
+    }
+    client
+  }
+
+  /// Verifies the ID token of a completed sign-in and makes it the session.
+  async fn keep_session(
+    &self,
+    provider: &Provider,
+    provider_name: String,
+    device: bool,
+    tokens: oauth::TokenResponse,
+    nonce: Option<&str>,
+    requested_scopes: Vec<String>,
+  ) -> crate::Result<GoogleSignInResponse> {
+    let id_token = tokens
+      .id_token
+      .ok_or(Error::InvalidIdToken("token response has no id_token"))?;
+    let claims = provider.verifier.verify(&id_token, nonce).await?;
+
+    // Google only returns a refresh token on first consent; keep the one we have
+    // when the same account signs in again.
+    let refresh_token = tokens.refresh_token.or_else(|| {
+      self
+        .sessions
+        .account(&claims.sub)
+        .filter(|session| session.provider == provider_name && session.device == device)
+        .and_then(|session| session.refresh_token)
+    });
+    let scopes = split_scopes(tokens.scope.as_deref()).unwrap_or(requested_scopes);
+
+    self.sessions.sign_in(StoredSession {
+      provider: provider_name,
+      device,
+      claims: claims.clone(),
+      id_token: id_token.clone(),
+      access_token: Some(tokens.access_token),
+      refresh_token,
+      expires_at: tokens.expires_in.map(|secs| unix_now() + secs),
+      scopes: scopes.clone(),
+    })?;
+
     Ok(GoogleSignInResponse {
-      success: true,
-      id_token: Some("desktop_placeholder_token".to_string()),
-      display_name: Some("Desktop User".to_string()),
-      given_name: Some("Desktop".to_string()),
-      family_name: Some("User".to_string()),
+      id_token: Some(id_token),
+      display_name: claims.name,
+      given_name: claims.given_name,
+      family_name: claims.family_name,
+      profile_picture_uri: claims.picture,
+      email: claims.email,
+      server_auth_code: None,
+      code_verifier: None,
+      redirect_uri: None,
+      granted_scopes: scopes,
+    })
+  }
+
+  /// Signs in with Apple and keeps its ID token as the session. The authorization
+  /// code is returned as `server_auth_code` for the server to redeem.
+  async fn sign_in_with_apple(&self, payload: GoogleSignInRequest) -> crate::Result<GoogleSignInResponse> {
+    let apple = self.apple.as_ref().ok_or(Error::MissingConfig("apple"))?;
+    let server = LoopbackServer::bind(self.options.redirect_ports.clone()).await?;
+    let (url, state) = apple.authorization_url(server.port(), &pkce::random_token(32), payload.nonce.as_deref())?;
+    let callback = self.redirect(server, &url, &state).await?;
+    let authorization = apple
+      .complete(callback, payload.nonce.as_deref(), &self.sessions)
+      .await?;
+
+    let claims = authorization.claims;
+    self.sessions.sign_in(StoredSession {
+      provider: apple::NAME.into(),
+      device: false,
+      claims: claims.clone(),
+      id_token: authorization.id_token.clone(),
+      access_token: None,
+      refresh_token: None,
+      expires_at: None,
+      scopes: apple::scopes(),
+    })?;
+
+    Ok(GoogleSignInResponse {
+      id_token: Some(authorization.id_token),
+      display_name: claims.name,
+      given_name: claims.given_name,
+      family_name: claims.family_name,
       profile_picture_uri: None,
-      email: Some("desktop@example.com".to_string()),
-      error: None,
+      email: claims.email,
+      server_auth_code: authorization.code,
+      code_verifier: None,
+      redirect_uri: None,
+      granted_scopes: apple::scopes(),
     })
   }
 
-  pub fn google_sign_out(&self) -> crate::Result<GoogleSignOutResponse> {
-    // Desktop implementation would clear stored tokens
-    Ok(GoogleSignOutResponse {
-      success: true,
-      error: None,
+  /// Sends the user through the browser consent for `client` and waits for the
+  /// authorization code on a loopback redirect.
+  async fn authorize(&self, client: &ClientConfig, params: &AuthorizationParams<'_>) -> crate::Result<Authorization> {
+    let pkce = Pkce::new();
+    let state = pkce::random_token(32);
+    let server = LoopbackServer::bind(self.options.redirect_ports.clone()).await?;
+    let redirect_uri = server.redirect_uri();
+
+    let url = oauth::authorization_url(
+      client,
+      &AuthorizationRequest {
+        redirect_uri: &redirect_uri,
+        state: &state,
+        code_challenge: &pkce.challenge,
+      },
+      params,
+    )?;
+    let callback = self.redirect(server, &url, &state).await?;
+    let code = callback.code.ok_or(Error::OAuth {
+      error: "invalid_response".into(),
+      description: Some("redirect is missing the authorization code".into()),
+    })?;
+
+    Ok(Authorization {
+      code,
+      code_verifier: pkce.verifier,
+      redirect_uri,
+      scope: callback.scope,
     })
   }
 
-  pub fn is_signed_in(&self) -> crate::Result<IsSignedInResponse> {
-    // Desktop implementation would check stored tokens
-    Ok(IsSignedInResponse {
-      is_signed_in: false,
-      error: None,
+  /// Opens `url` in the browser and waits for the redirect carrying `state` on
+  /// `server`, for at most the sign-in timeout.
+  async fn redirect(&self, server: LoopbackServer, url: &Url, state: &str) -> crate::Result<CallbackParams> {
+    let deadline = Instant::now() + self.options.sign_in_timeout.unwrap_or_default();
+    match &self.options.open_url {
+      Some(open_url) => open_url(url.as_str())?,
+      None => open::that_detached(url.as_str())?,
+    }
+
+    let mut callback = server.wait_for_callback(state, deadline).await?;
+    if let Some(error) = callback.error.take() {
+      return Err(match error.as_str() {
+        // Apple reports a closed consent sheet as `user_cancelled_authorize`.
+        "access_denied" | "user_cancelled_authorize" => Error::Cancelled,
+        _ => Error::OAuth {
+          error,
+          description: callback.error_description,
+        },
+      });
+    }
+    Ok(callback)
+  }
+}
+
+fn tokens_response(session: StoredSession) -> GetTokensResponse {
+  GetTokensResponse {
+    id_token: session.id_token,
+    access_token: session.access_token,
+    expires_at: session.expires_at,
+  }
+}
+
+fn cached(session: StoredSession) -> FetchedTokens {
+  FetchedTokens {
+    tokens: tokens_response(session),
+    refreshed: false,
+  }
+}
+
+/// Authorization code received on the loopback redirect, not yet redeemed.
+struct Authorization {
+  code: String,
+  code_verifier: String,
+  redirect_uri: String,
+  scope: Option<String>,
+}
+
+impl Authorization {
+  async fn exchange(&self, http: &reqwest::Client, client: &ClientConfig) -> crate::Result<oauth::TokenResponse> {
+    oauth::exchange_code(http, client, &self.code, &self.code_verifier, &self.redirect_uri).await
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{
+      atomic::{AtomicUsize, Ordering},
+      Arc, Mutex,
+    },
+    time::Duration,
+  };
+
+  use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+  use serde_json::{json, Value};
+  use sha2::{Digest, Sha256};
+  use tokio::net::TcpStream;
+
+  use super::*;
+  use crate::{
+    loopback::REQUEST_TIMEOUT,
+    test_support::{claims, jwks, keys, temp_dir, Request, Response, StandIn},
+    Builder,
+  };
+
+  const CLIENT_ID: &str = "desktop-client.apps.googleusercontent.com";
+
+  /// Authorization requests the stand-in approved, by the code it issued.
+  type Approved = Arc<Mutex<HashMap<String, HashMap<String, String>>>>;
+
+  /// A stand-in for Google: `/authorize` approves at once and redirects back
+  /// with a code, `/token` redeems it against the PKCE challenge.
+  async fn identity_provider() -> StandIn {
+    identity_provider_with(|_| None).await
+  }
+
+  /// [`identity_provider`] with `extra` answering the requests it wants to.
+  async fn identity_provider_with(extra: impl Fn(&Request) -> Option<Response> + Send + Sync + 'static) -> StandIn {
+    let approved = Approved::default();
+    let url = Arc::new(Mutex::new(String::new()));
+    let issuer = url.clone();
+    let idp = StandIn::start(move |request| {
+      if let Some(response) = extra(request) {
+        return response;
+      }
+      match request.path.as_str() {
+        "/authorize" => authorize(request, &approved),
+        "/token" => token(request, &approved, &issuer.lock().unwrap()),
+        "/jwks" => Response::json(jwks(&[&keys()[0]])),
+        _ => Response::not_found(),
+      }
+    })
+    .await;
+    *url.lock().unwrap() = idp.url.clone();
+    idp
+  }
+
+  fn authorize(request: &Request, approved: &Approved) -> Response {
+    let code = pkce::random_token(16);
+    approved.lock().unwrap().insert(code.clone(), request.query.clone());
+    let mut location = Url::parse(&request.query["redirect_uri"]).unwrap();
+    location
+      .query_pairs_mut()
+      .append_pair("code", &code)
+      .append_pair("state", &request.query["state"])
+      .append_pair("scope", "openid email profile");
+    Response::redirect(location.as_str())
+  }
+
+  fn token(request: &Request, approved: &Approved, issuer: &str) -> Response {
+    let Some(authorization) = approved.lock().unwrap().remove(&request.form["code"]) else {
+      return Response::json(json!({ "error": "invalid_grant" })).status(400);
+    };
+    let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(request.form["code_verifier"].as_bytes()));
+    if challenge != authorization["code_challenge"] || request.form["redirect_uri"] != authorization["redirect_uri"] {
+      return Response::json(json!({ "error": "invalid_grant" })).status(400);
+    }
+    let mut claims = claims(issuer, CLIENT_ID);
+    claims["nonce"] = authorization["nonce"].clone().into();
+    Response::json(json!({
+      "access_token": "access-token",
+      "refresh_token": "refresh-token",
+      "expires_in": 3600,
+      "scope": "openid email profile",
+      "id_token": keys()[0].sign(&claims),
+    }))
+  }
+
+  type Browse = Box<dyn Fn(String) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
+  /// A desktop backend signing in against `idp`, whose browser runs `browse`
+  /// with the authorization URL.
+  fn backend(idp: &StandIn, timeout: Duration, browse: Browse) -> DesktopBackend {
+    let Builder { options, .. } = Builder::new()
+      .client_id(CLIENT_ID)
+      .authorization_endpoint(format!("{}/authorize", idp.url))
+      .token_endpoint(format!("{}/token", idp.url))
+      .jwks_uri(format!("{}/jwks", idp.url))
+      .revocation_endpoint(format!("{}/revoke", idp.url))
+      .issuers([idp.url.clone()])
+      .sign_in_timeout(timeout)
+      .open_url_with(move |url| {
+        tokio::spawn(browse(url.to_string()));
+        Ok(())
+      });
+    let options = options.resolve(Config::default()).unwrap();
+    DesktopBackend::new(options, &temp_dir()).unwrap()
+  }
+
+  /// A backend signed in against `idp`, with a browser that approves at once.
+  async fn signed_in(idp: &StandIn) -> DesktopBackend {
+    let backend = backend(idp, Duration::from_secs(10), Box::new(|url| Box::pin(async move { approve(&url).await })));
+    backend.sign_in(request("n-0")).await.unwrap();
+    backend
+  }
+
+  /// Follows the authorization URL like a browser, redirect included.
+  async fn approve(url: &str) {
+    let response = reqwest::get(url).await.unwrap();
+    assert_eq!(response.status(), 200);
+  }
+
+  fn request(nonce: &str) -> GoogleSignInRequest {
+    GoogleSignInRequest {
+      provider: None,
+      filter_by_authorized_accounts: None,
+      auto_select_enabled: None,
+      nonce: Some(nonce.into()),
+      server_client_id: None,
+      request_server_auth_code: None,
+      force_code_for_refresh_token: None,
+    }
+  }
+
+  fn redirect_uri(url: &str) -> String {
+    let url = Url::parse(url).unwrap();
+    let (_, redirect_uri) = url.query_pairs().find(|(name, _)| name == "redirect_uri").unwrap();
+    redirect_uri.into_owned()
+  }
+
+  #[tokio::test]
+  async fn signs_in_with_the_right_state() {
+    let idp = identity_provider().await;
+    let backend = backend(&idp, Duration::from_secs(10), Box::new(|url| Box::pin(async move { approve(&url).await })));
+
+    let response = backend.sign_in(request("n-1")).await.unwrap();
+    assert_eq!(response.email.as_deref(), Some("ada@be-out.app"));
+    assert!(backend.is_signed_in().await.unwrap());
+    assert_eq!(backend.claims().unwrap().nonce.as_deref(), Some("n-1"));
+    assert_eq!(idp.hits("/token"), 1);
+  }
+
+  #[tokio::test]
+  async fn keeps_waiting_after_a_wrong_or_missing_state() {
+    let idp = identity_provider().await;
+    let backend = backend(
+      &idp,
+      Duration::from_secs(10),
+      Box::new(|url| {
+        Box::pin(async move {
+          let redirect_uri = redirect_uri(&url);
+          for forged in ["?code=forged&state=wrong", "?code=forged", ""] {
+            let response = reqwest::get(format!("{redirect_uri}/{forged}")).await.unwrap();
+            assert_eq!(response.status(), 400);
+          }
+          approve(&url).await;
+        })
+      }),
+    );
+
+    let response = backend.sign_in(request("n-2")).await.unwrap();
+    assert_eq!(response.email.as_deref(), Some("ada@be-out.app"));
+    // Only the genuine code reached the token endpoint.
+    assert_eq!(idp.hits("/token"), 1);
+  }
+
+  #[tokio::test]
+  async fn is_not_held_up_by_a_preconnect() {
+    let idp = identity_provider().await;
+    let backend = backend(
+      &idp,
+      Duration::from_secs(10),
+      Box::new(|url| {
+        Box::pin(async move {
+          let port = Url::parse(&redirect_uri(&url)).unwrap().port().unwrap();
+          // Opened and left idle for the whole sign-in, like a browser's spare connection.
+          let idle = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+          approve(&url).await;
+          drop(idle);
+        })
+      }),
+    );
+
+    // Key generation is slow in debug builds; keep it out of the measurement.
+    keys();
+    let started = Instant::now();
+    backend.sign_in(request("n-3")).await.unwrap();
+    assert!(started.elapsed() < REQUEST_TIMEOUT);
+  }
+
+  #[tokio::test]
+  async fn times_out_without_a_redirect() {
+    let idp = identity_provider().await;
+    let backend = backend(&idp, Duration::from_millis(300), Box::new(|_| Box::pin(async {})));
+
+    assert!(matches!(backend.sign_in(request("n-4")).await, Err(Error::TimedOut)));
+    assert!(!backend.is_signed_in().await.unwrap());
+    assert_eq!(idp.hits("/token"), 0);
+  }
+
+  #[tokio::test]
+  async fn revokes_the_grant_and_forgets_the_account() {
+    let idp = identity_provider_with(|request| {
+      (request.path == "/revoke").then(|| match request.form.get("token").map(String::as_str) {
+        Some("refresh-token") => Response::json(Value::Null),
+        _ => Response::json(json!({ "error": "invalid_request" })).status(400),
+      })
     })
+    .await;
+    let backend = signed_in(&idp).await;
+
+    backend.revoke().await.unwrap();
+    assert!(!backend.is_signed_in().await.unwrap());
+    assert!(backend.accounts().is_empty());
+    assert_eq!(idp.hits("/revoke"), 1);
+  }
+
+  #[tokio::test]
+  async fn keeps_the_session_when_revocation_fails() {
+    let idp = identity_provider_with(|request| {
+      (request.path == "/revoke").then(|| {
+        Response::json(json!({ "error": "invalid_request", "error_description": "malformed token" })).status(400)
+      })
+    })
+    .await;
+    let backend = signed_in(&idp).await;
+
+    assert!(matches!(
+      backend.revoke().await,
+      Err(Error::OAuth { error, description })
+        if error == "invalid_request" && description.as_deref() == Some("malformed token")
+    ));
+    assert!(backend.is_signed_in().await.unwrap());
+  }
+
+  /// A stand-in that renews tokens, counting the refresh grants it serves.
+  async fn refreshing_identity_provider() -> (StandIn, Arc<AtomicUsize>) {
+    let refreshes = Arc::new(AtomicUsize::new(0));
+    let served = refreshes.clone();
+    let idp = identity_provider_with(move |request| {
+      (request.form.get("grant_type").map(String::as_str) == Some("refresh_token")).then(|| {
+        served.fetch_add(1, Ordering::SeqCst);
+        Response::json(json!({ "access_token": "refreshed-access-token", "expires_in": 3600 }))
+      })
+    })
+    .await;
+    (idp, refreshes)
+  }
+
+  #[tokio::test]
+  async fn shares_one_refresh_between_concurrent_callers() {
+    let (idp, refreshes) = refreshing_identity_provider().await;
+    let backend = signed_in(&idp).await;
+
+    let (first, second) = tokio::join!(backend.tokens(true), backend.tokens(true));
+    let (first, second) = (first.unwrap(), second.unwrap());
+    assert_eq!(refreshes.load(Ordering::SeqCst), 1);
+    assert_eq!(first.tokens.access_token.as_deref(), Some("refreshed-access-token"));
+    assert_eq!(second.tokens.access_token.as_deref(), Some("refreshed-access-token"));
+    assert!(first.refreshed != second.refreshed);
+  }
+
+  #[tokio::test]
+  async fn forgets_an_expired_session_it_cannot_refresh() {
+    let (idp, refreshes) = refreshing_identity_provider().await;
+    let backend = signed_in(&idp).await;
+    let mut session = backend.sessions.get().unwrap();
+    session.refresh_token = None;
+    backend.sessions.save(session.clone()).unwrap();
+
+    // Still valid: handed out as is, even when a refresh is asked for.
+    let fetched = backend.tokens(true).await.unwrap();
+    assert_eq!(fetched.tokens.access_token.as_deref(), Some("access-token"));
+    assert!(!fetched.refreshed);
+    assert!(backend.is_signed_in().await.unwrap());
+
+    session.expires_at = Some(unix_now() - 1);
+    backend.sessions.save(session).unwrap();
+    assert!(matches!(backend.tokens(false).await, Err(Error::NotSignedIn)));
+    assert!(!backend.is_signed_in().await.unwrap());
+    assert!(backend.accounts().is_empty());
+    assert_eq!(refreshes.load(Ordering::SeqCst), 0);
   }
 }
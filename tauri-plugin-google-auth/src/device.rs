@@ -0,0 +1,214 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::{
+  backend::DeviceAuthorization,
+  oauth::{self, unix_now, ClientConfig, TokenResponse},
+  Error,
+};
+
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+/// Polling interval when the server does not name one (RFC 8628, section 3.2).
+const DEFAULT_INTERVAL: u64 = 5;
+/// Added to the interval on every `slow_down` (RFC 8628, section 3.5).
+const SLOW_DOWN_STEP: Duration = Duration::from_secs(5);
+
+/// Device authorization response (RFC 8628, section 3.2).
+#[derive(Debug, Deserialize)]
+struct DeviceAuthorizationResponse {
+  device_code: String,
+  user_code: String,
+  /// Google calls it `verification_url`.
+  #[serde(alias = "verification_url")]
+  verification_uri: String,
+  #[serde(default)]
+  verification_uri_complete: Option<String>,
+  expires_in: u64,
+  #[serde(default)]
+  interval: Option<u64>,
+}
+
+/// Asks `client`'s provider for a device and user code.
+pub(crate) async fn start(
+  http: &reqwest::Client,
+  client: &ClientConfig,
+  provider: &str,
+) -> crate::Result<DeviceAuthorization> {
+  let endpoint = client
+    .device_authorization_endpoint
+    .as_deref()
+    .ok_or(Error::MissingConfig("device_authorization_endpoint"))?;
+  let scope = client.scopes.join(" ");
+  let response = http
+    .post(endpoint)
+    .form(&[("client_id", client.client_id()?), ("scope", scope.as_str())])
+    .send()
+    .await?;
+  if !response.status().is_success() {
+    return Err(oauth::error_response(response, "device authorization endpoint").await);
+  }
+  let response: DeviceAuthorizationResponse = response.json().await?;
+
+  Ok(DeviceAuthorization {
+    provider: provider.to_string(),
+    device_code: response.device_code,
+    user_code: response.user_code,
+    verification_uri: response.verification_uri,
+    verification_uri_complete: response.verification_uri_complete,
+    expires_at: unix_now() + response.expires_in,
+    interval: Duration::from_secs(response.interval.unwrap_or(DEFAULT_INTERVAL)),
+  })
+}
+
+/// Polls the token endpoint until the user approves or denies the request, or the
+/// device code expires.
+pub(crate) async fn poll(
+  http: &reqwest::Client,
+  client: &ClientConfig,
+  authorization: &DeviceAuthorization,
+) -> crate::Result<TokenResponse> {
+  let mut interval = authorization.interval;
+  let mut form = vec![
+    ("grant_type", DEVICE_CODE_GRANT),
+    ("device_code", authorization.device_code.as_str()),
+    ("client_id", client.client_id()?),
+  ];
+  if let Some(secret) = client.client_secret.as_deref() {
+    form.push(("client_secret", secret));
+  }
+
+  loop {
+    if unix_now() + interval.as_secs() >= authorization.expires_at {
+      return Err(Error::TimedOut);
+    }
+    tokio::time::sleep(interval).await;
+
+    match oauth::token_request(http, &client.token_endpoint, &form).await {
+      Ok(tokens) => return Ok(tokens),
+      Err(Error::OAuth { error, description }) => match error.as_str() {
+        "authorization_pending" => {}
+        "slow_down" => interval += SLOW_DOWN_STEP,
+        "access_denied" => return Err(Error::Cancelled),
+        "expired_token" => return Err(Error::TimedOut),
+        _ => return Err(Error::OAuth { error, description }),
+      },
+      Err(e) => return Err(e),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    sync::{
+      atomic::{AtomicUsize, Ordering},
+      Arc, Mutex,
+    },
+    time::Instant,
+  };
+
+  use serde_json::json;
+
+  use super::*;
+  use crate::test_support::{Response, StandIn};
+
+  const CLIENT_ID: &str = "kiosk.apps.googleusercontent.com";
+  const INTERVAL: Duration = Duration::from_millis(10);
+
+  /// A token endpoint answering its `n`th poll, from 0, with the error
+  /// `errors(n)`, or with tokens when that is `None`. Returns the stand-in and
+  /// when each poll arrived.
+  async fn token_endpoint(
+    errors: impl Fn(usize) -> Option<&'static str> + Send + Sync + 'static,
+  ) -> (StandIn, Arc<Mutex<Vec<Instant>>>) {
+    let polls = AtomicUsize::new(0);
+    let arrivals = Arc::new(Mutex::new(Vec::new()));
+    let arrived = arrivals.clone();
+    let idp = StandIn::start(move |request| {
+      arrived.lock().unwrap().push(Instant::now());
+      if request.form.get("grant_type").map(String::as_str) != Some(DEVICE_CODE_GRANT)
+        || request.form.get("device_code").map(String::as_str) != Some("device-code")
+      {
+        return Response::json(json!({ "error": "invalid_request" })).status(400);
+      }
+      match errors(polls.fetch_add(1, Ordering::SeqCst)) {
+        Some(error) => Response::json(json!({ "error": error })).status(400),
+        None => Response::json(json!({ "access_token": "access-token", "expires_in": 3600 })),
+      }
+    })
+    .await;
+    (idp, arrivals)
+  }
+
+  fn client(idp: &StandIn) -> ClientConfig {
+    ClientConfig {
+      client_id: Some(CLIENT_ID.into()),
+      token_endpoint: format!("{}/token", idp.url),
+      ..Default::default()
+    }
+  }
+
+  async fn poll_until_done(idp: &StandIn) -> crate::Result<TokenResponse> {
+    poll(&reqwest::Client::new(), &client(idp), &authorization(60)).await
+  }
+
+  fn authorization(expires_in: u64) -> DeviceAuthorization {
+    DeviceAuthorization {
+      provider: "google".into(),
+      device_code: "device-code".into(),
+      user_code: "WDJB-MJHT".into(),
+      verification_uri: "https://www.google.com/device".into(),
+      verification_uri_complete: None,
+      expires_at: unix_now() + expires_in,
+      interval: INTERVAL,
+    }
+  }
+
+  #[tokio::test]
+  async fn keeps_polling_while_authorization_is_pending() {
+    let (idp, _) = token_endpoint(|n| (n < 2).then_some("authorization_pending")).await;
+
+    let tokens = poll_until_done(&idp).await.unwrap();
+    assert_eq!(tokens.access_token, "access-token");
+    assert_eq!(idp.hits("/token"), 3);
+  }
+
+  #[tokio::test]
+  async fn backs_off_when_told_to_slow_down() {
+    let (idp, arrivals) = token_endpoint(|n| (n == 0).then_some("slow_down")).await;
+
+    poll_until_done(&idp).await.unwrap();
+    let arrivals = arrivals.lock().unwrap();
+    assert_eq!(arrivals.len(), 2);
+    assert!(arrivals[1] - arrivals[0] >= INTERVAL + SLOW_DOWN_STEP);
+  }
+
+  #[tokio::test]
+  async fn stops_when_the_device_code_expires() {
+    let (idp, _) = token_endpoint(|n| Some(if n == 0 { "authorization_pending" } else { "expired_token" })).await;
+
+    assert!(matches!(poll_until_done(&idp).await, Err(Error::TimedOut)));
+    assert_eq!(idp.hits("/token"), 2);
+
+    // A code that expires before the next poll is not polled at all.
+    let result = poll(&reqwest::Client::new(), &client(&idp), &authorization(0)).await;
+    assert!(matches!(result, Err(Error::TimedOut)));
+    assert_eq!(idp.hits("/token"), 2);
+  }
+
+  #[tokio::test]
+  async fn reports_a_denied_request_as_cancelled() {
+    let (idp, _) = token_endpoint(|n| Some(if n == 0 { "authorization_pending" } else { "access_denied" })).await;
+
+    assert!(matches!(poll_until_done(&idp).await, Err(Error::Cancelled)));
+  }
+
+  #[tokio::test]
+  async fn fails_on_other_errors() {
+    let (idp, _) = token_endpoint(|_| Some("invalid_client")).await;
+
+    assert!(matches!(poll_until_done(&idp).await, Err(Error::OAuth { error, .. }) if error == "invalid_client"));
+    assert_eq!(idp.hits("/token"), 1);
+  }
+}
@@ -1,16 +1,141 @@
-use serde::{ser::Serializer, Serialize};
+use serde::{ser::Serializer, Deserialize, Serialize};
+use serde_json::{json, Value};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Stable, machine-readable error codes exposed to the webview.
+///
+/// New codes may be added; existing ones are never renamed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+  /// The user dismissed the account picker or consent screen.
+  Cancelled,
+  /// No Google account is available on the device.
+  NoAccount,
+  /// The identity provider could not be reached.
+  Network,
+  /// Client IDs, endpoints or platform setup are wrong or missing.
+  Misconfigured,
+  /// The ID token's `nonce` does not belong to this sign-in attempt.
+  NonceMismatch,
+  /// The OAuth redirect carried an unexpected `state`.
+  StateMismatch,
+  /// The ID token failed signature or claim validation.
+  InvalidIdToken,
+  /// The operation needs a signed-in account.
+  NotSignedIn,
+  /// The identity provider rejected the request.
+  OauthError,
+  /// The user did not finish signing in within the configured time.
+  Timeout,
+  /// The account is refused by the sign-in policy in the plugin configuration.
+  PolicyViolation,
+  UnsupportedPlatform,
+  Internal,
+}
+
+impl ErrorCode {
+  /// Maps the `errorCode` reported by the Kotlin and Swift plugins.
+  #[cfg_attr(desktop, allow(dead_code))]
+  pub(crate) fn from_native(code: Option<&str>) -> Self {
+    match code {
+      Some("cancelled") => Self::Cancelled,
+      Some("no_account") => Self::NoAccount,
+      Some("network") => Self::Network,
+      Some("misconfigured") => Self::Misconfigured,
+      Some("not_signed_in") => Self::NotSignedIn,
+      _ => Self::Internal,
+    }
+  }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
   #[error(transparent)]
   Io(#[from] std::io::Error),
+  #[error(transparent)]
+  Tauri(#[from] tauri::Error),
+  #[error(transparent)]
+  Http(#[from] reqwest::Error),
+  #[error(transparent)]
+  Url(#[from] url::ParseError),
+  #[error(transparent)]
+  Jwt(#[from] jsonwebtoken::errors::Error),
   #[cfg(mobile)]
   #[error(transparent)]
   PluginInvoke(#[from] tauri::plugin::mobile::PluginInvokeError),
   #[error("Unsupported platform")]
   UnsupportedPlatform,
+  #[error("Missing plugin configuration value `{0}`")]
+  MissingConfig(&'static str),
+  #[error("Invalid plugin configuration: {0}")]
+  InvalidConfig(String),
+  #[error("Sign-in was cancelled")]
+  Cancelled,
+  #[error("Sign-in timed out")]
+  TimedOut,
+  #[error("OAuth error `{error}`{}", description.as_deref().map(|d| format!(": {d}")).unwrap_or_default())]
+  OAuth {
+    error: String,
+    description: Option<String>,
+  },
+  #[error("No account is signed in")]
+  NotSignedIn,
+  #[error("No stored account with ID `{0}`")]
+  UnknownAccount(String),
+  #[error("OAuth state mismatch")]
+  StateMismatch,
+  #[error("Invalid ID token: {0}")]
+  InvalidIdToken(&'static str),
+  #[error("ID token nonce does not match the sign-in attempt")]
+  NonceMismatch,
+  #[error("Nonce was already used by another sign-in attempt")]
+  NonceReused,
+  /// Names the rule of the sign-in policy the account broke.
+  #[error("Account is not allowed by the sign-in policy ({0})")]
+  PolicyViolation(&'static str),
+  /// Failure reported by the Kotlin or Swift side of the plugin.
+  #[error("{message}")]
+  Native { code: ErrorCode, message: String },
+}
+
+impl Error {
+  pub fn code(&self) -> ErrorCode {
+    match self {
+      Self::Io(_) | Self::Tauri(_) => ErrorCode::Internal,
+      Self::Http(_) => ErrorCode::Network,
+      Self::Url(_) | Self::MissingConfig(_) | Self::InvalidConfig(_) => ErrorCode::Misconfigured,
+      Self::Jwt(_) | Self::InvalidIdToken(_) => ErrorCode::InvalidIdToken,
+      #[cfg(mobile)]
+      Self::PluginInvoke(_) => ErrorCode::Internal,
+      Self::UnsupportedPlatform => ErrorCode::UnsupportedPlatform,
+      Self::Cancelled => ErrorCode::Cancelled,
+      Self::TimedOut => ErrorCode::Timeout,
+      Self::OAuth { error, .. } => match error.as_str() {
+        "access_denied" => ErrorCode::Cancelled,
+        "invalid_client" | "unauthorized_client" | "redirect_uri_mismatch" => ErrorCode::Misconfigured,
+        _ => ErrorCode::OauthError,
+      },
+      Self::NotSignedIn | Self::UnknownAccount(_) => ErrorCode::NotSignedIn,
+      Self::StateMismatch => ErrorCode::StateMismatch,
+      Self::NonceMismatch | Self::NonceReused => ErrorCode::NonceMismatch,
+      Self::PolicyViolation(_) => ErrorCode::PolicyViolation,
+      Self::Native { code, .. } => *code,
+    }
+  }
+
+  /// Extra context for the webview, when there is any.
+  pub fn details(&self) -> Option<Value> {
+    match self {
+      Self::MissingConfig(key) => Some(json!({ "key": key })),
+      Self::PolicyViolation(rule) => Some(json!({ "rule": rule })),
+      Self::OAuth { error, description } => Some(json!({ "error": error, "description": description })),
+      Self::Jwt(e) => Some(json!({ "reason": format!("{:?}", e.kind()) })),
+      Self::Http(e) => e.status().map(|status| json!({ "status": status.as_u16() })),
+      _ => None,
+    }
+  }
 }
 
 impl Serialize for Error {
@@ -18,6 +143,18 @@ impl Serialize for Error {
   where
     S: Serializer,
   {
-    serializer.serialize_str(self.to_string().as_ref())
+    #[derive(Serialize)]
+    struct Payload {
+      code: ErrorCode,
+      message: String,
+      details: Option<Value>,
+    }
+
+    Payload {
+      code: self.code(),
+      message: self.to_string(),
+      details: self.details(),
+    }
+    .serialize(serializer)
   }
 }
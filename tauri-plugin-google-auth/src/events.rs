@@ -0,0 +1,42 @@
+use tauri::{AppHandle, Emitter, Runtime};
+use tokio::sync::broadcast;
+
+use crate::models::{AccountSummary, AuthState, AuthStateChange};
+
+/// Event emitted to every webview whenever the auth state changes.
+pub const STATE_CHANGED_EVENT: &str = "google-auth://state-changed";
+
+/// Changes buffered per Rust subscriber before the slowest one starts lagging.
+const CHANNEL_CAPACITY: usize = 16;
+
+/// Fans auth state changes out to the webviews and to Rust subscribers.
+pub(crate) struct StateEvents<R: Runtime> {
+  app: AppHandle<R>,
+  sender: broadcast::Sender<AuthStateChange>,
+}
+
+impl<R: Runtime> StateEvents<R> {
+  pub fn new(app: AppHandle<R>) -> Self {
+    Self {
+      app,
+      sender: broadcast::channel(CHANNEL_CAPACITY).0,
+    }
+  }
+
+  pub fn emit(&self, state: AuthState, account: Option<AccountSummary>, active_account: Option<AccountSummary>) {
+    let change = AuthStateChange {
+      state,
+      account,
+      active_account,
+    };
+    if let Err(e) = self.app.emit(STATE_CHANGED_EVENT, &change) {
+      log::warn!("Failed to emit {STATE_CHANGED_EVENT}: {e}");
+    }
+    // Having no Rust subscribers is not an error.
+    let _ = self.sender.send(change);
+  }
+
+  pub fn subscribe(&self) -> broadcast::Receiver<AuthStateChange> {
+    self.sender.subscribe()
+  }
+}
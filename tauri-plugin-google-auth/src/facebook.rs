@@ -0,0 +1,92 @@
+use serde::Deserialize;
+
+use crate::{
+  config::FacebookConfig,
+  id_token::{IdTokenVerifier, VerifierConfig},
+  oauth::ClientConfig,
+  oidc::Provider,
+  Error,
+};
+
+pub(crate) const NAME: &str = "facebook";
+
+const DEFAULT_GRAPH_API_VERSION: &str = "v19.0";
+const ISSUER: &str = "https://www.facebook.com";
+const JWKS_URI: &str = "https://limited.facebook.com/.well-known/oauth/openid/jwks/";
+/// Graph API error code of an access token that is expired or was invalidated.
+const INVALID_TOKEN_CODE: i64 = 190;
+
+/// Facebook Login as an OpenID Connect provider. Facebook publishes no usable
+/// discovery document for the manual flow, so its endpoints are fixed here.
+pub(crate) fn provider(http: &reqwest::Client, config: &FacebookConfig) -> Provider {
+  let version = graph_api_version(config);
+  Provider {
+    client: ClientConfig {
+      client_id: Some(config.app_id.clone()),
+      client_secret: None,
+      authorization_endpoint: format!("https://www.facebook.com/{version}/dialog/oauth"),
+      token_endpoint: format!("https://graph.facebook.com/{version}/oauth/access_token"),
+      // Permissions are revoked through the Graph API, see [`revoke`].
+      revocation_endpoint: None,
+      device_authorization_endpoint: None,
+      scopes: ["openid", "email", "public_profile"].map(String::from).to_vec(),
+      hosted_domain: None,
+    },
+    verifier: IdTokenVerifier::new(
+      http.clone(),
+      VerifierConfig {
+        jwks_uri: JWKS_URI.into(),
+        issuers: vec![ISSUER.into()],
+        audiences: vec![config.app_id.clone()],
+      },
+    ),
+  }
+}
+
+#[derive(Deserialize)]
+struct GraphErrorResponse {
+  error: GraphError,
+}
+
+#[derive(Deserialize)]
+struct GraphError {
+  message: String,
+  #[serde(default)]
+  code: i64,
+}
+
+/// Removes every permission the user granted the app, which logs them out of it
+/// everywhere. A token Facebook no longer accepts counts as revoked.
+pub(crate) async fn revoke(http: &reqwest::Client, config: &FacebookConfig, access_token: &str) -> crate::Result<()> {
+  let response = http
+    .delete(format!(
+      "https://graph.facebook.com/{}/me/permissions",
+      graph_api_version(config)
+    ))
+    .query(&[("access_token", access_token)])
+    .send()
+    .await?;
+  if response.status().is_success() {
+    return Ok(());
+  }
+
+  let status = response.status();
+  match response.json::<GraphErrorResponse>().await {
+    Ok(body) if body.error.code == INVALID_TOKEN_CODE => Ok(()),
+    Ok(body) => Err(Error::OAuth {
+      error: format!("graph_error_{}", body.error.code),
+      description: Some(body.error.message),
+    }),
+    Err(_) => Err(Error::OAuth {
+      error: "http_error".into(),
+      description: Some(format!("permissions endpoint returned {status}")),
+    }),
+  }
+}
+
+fn graph_api_version(config: &FacebookConfig) -> &str {
+  config
+    .graph_api_version
+    .as_deref()
+    .unwrap_or(DEFAULT_GRAPH_API_VERSION)
+}
@@ -0,0 +1,464 @@
+use std::{collections::HashMap, future::Future, marker::PhantomData, sync::Mutex, time::Duration};
+
+use tauri::{AppHandle, Manager, Runtime};
+use tokio::sync::{broadcast, watch};
+
+use crate::{
+  avatar::AvatarCache,
+  backend::{AuthBackend, DeviceAuthorization},
+  config::AccountPolicy,
+  events::StateEvents,
+  id_token::IdTokenVerifier,
+  models::*,
+  nonce::NonceRegistry,
+  oauth, Error, ErrorCode,
+};
+
+/// Name of the provider whose ID tokens the plugin verifies itself.
+const GOOGLE: &str = "google";
+
+/// Access to the google-auth APIs.
+pub struct GoogleAuth<R: Runtime> {
+  backend: Box<dyn AuthBackend>,
+  verifier: IdTokenVerifier,
+  nonces: NonceRegistry,
+  events: StateEvents<R>,
+  sign_in_timeout: Duration,
+  policy: AccountPolicy,
+  /// Bumped by [`GoogleAuth::cancel_sign_in`]; attempts abort when it changes.
+  cancel: watch::Sender<u64>,
+  /// Claims of the last sign-in, for backends that keep no session of their own.
+  profile: Mutex<Option<IdTokenClaims>>,
+  avatars: Option<AvatarCache>,
+  /// Outcome of the startup restore, `None` while it is running.
+  restored: watch::Sender<Option<RestoredSession>>,
+  /// Device sign-ins started but not yet completed, by user code.
+  devices: Mutex<HashMap<String, DeviceAuthorization>>,
+  _marker: PhantomData<fn() -> R>,
+}
+
+impl<R: Runtime> GoogleAuth<R> {
+  pub(crate) fn new(
+    app: &AppHandle<R>,
+    backend: Box<dyn AuthBackend>,
+    verifier: IdTokenVerifier,
+    sign_in_timeout: Duration,
+    policy: AccountPolicy,
+  ) -> Self {
+    Self {
+      backend,
+      verifier,
+      nonces: NonceRegistry::default(),
+      events: StateEvents::new(app.clone()),
+      sign_in_timeout,
+      policy,
+      cancel: watch::channel(0).0,
+      profile: Mutex::new(None),
+      avatars: app
+        .path()
+        .app_cache_dir()
+        .ok()
+        .map(|dir| AvatarCache::new(dir.join("google-auth").join("avatars"), reqwest::Client::new())),
+      restored: watch::channel(None).0,
+      devices: Mutex::new(HashMap::new()),
+      _marker: PhantomData,
+    }
+  }
+
+  /// Receives every auth state change, in the order they are emitted to the webviews.
+  pub fn subscribe(&self) -> broadcast::Receiver<AuthStateChange> {
+    self.events.subscribe()
+  }
+
+  pub async fn ping(&self, payload: PingRequest) -> crate::Result<PingResponse> {
+    self.backend.ping(payload).await
+  }
+
+  /// Signs in interactively, binding the ID token to a fresh or caller-provided nonce.
+  pub async fn google_sign_in(&self, mut payload: GoogleSignInRequest) -> crate::Result<GoogleSignInResponse> {
+    let nonce = self.nonces.begin(payload.nonce.take())?;
+    payload.nonce = Some(nonce.as_str().to_string());
+    let provider = payload.provider.clone();
+
+    let response = self.interactive(self.backend.sign_in(payload)).await?;
+    let Some(id_token) = response.id_token.as_deref() else {
+      // Server auth code only: nothing was signed in locally.
+      return Ok(response);
+    };
+    let expected_nonce = self.backend.binds_nonce().then(|| nonce.as_str());
+    let claims = match self.verify(provider.as_deref(), id_token, expected_nonce).await {
+      Ok(claims) => claims,
+      Err(e) => return Err(self.discard(e).await),
+    };
+    self.enforce_policy(&claims).await?;
+    self.signed_in(&response, claims);
+    Ok(response)
+  }
+
+  /// Starts a sign-in that the user completes on another device, for screens
+  /// without a usable browser. Show the user code and verification URI (or a QR
+  /// code of [`DeviceSignInResponse::qr_payload`]), then await
+  /// [`GoogleAuth::complete_device_sign_in`].
+  pub async fn start_device_sign_in(&self, request: DeviceSignInRequest) -> crate::Result<DeviceSignInResponse> {
+    let authorization = self.backend.start_device_sign_in(request).await?;
+    let response = authorization.response();
+    let mut devices = self.devices.lock().unwrap();
+    devices.retain(|_, pending| pending.expires_at > oauth::unix_now());
+    devices.insert(authorization.user_code.clone(), authorization);
+    Ok(response)
+  }
+
+  /// Waits for the user to approve the device sign-in started for `user_code` and
+  /// signs in like [`GoogleAuth::google_sign_in`]. Fails with `timeout` when the
+  /// code expires; [`GoogleAuth::cancel_sign_in`] stops the polling.
+  pub async fn complete_device_sign_in(&self, user_code: &str) -> crate::Result<GoogleSignInResponse> {
+    let authorization = self.devices.lock().unwrap().remove(user_code).ok_or_else(|| Error::OAuth {
+      error: "invalid_request".into(),
+      description: Some("no device sign-in is pending for this user code".into()),
+    })?;
+
+    let provider = authorization.provider.clone();
+    let mut cancelled = self.cancel.subscribe();
+    let response = tokio::select! {
+      result = self.backend.complete_device_sign_in(authorization) => result?,
+      _ = cancelled.changed() => return Err(Error::Cancelled),
+    };
+    if let Some(id_token) = response.id_token.as_deref() {
+      let claims = match self.verify(Some(&provider), id_token, None).await {
+        Ok(claims) => claims,
+        Err(e) => return Err(self.discard(e).await),
+      };
+      self.enforce_policy(&claims).await?;
+      self.signed_in(&response, claims);
+    }
+    Ok(response)
+  }
+
+  /// Verifies the signature and standard claims of an ID token the backend
+  /// returned, so the policy and the profile only ever see checked claims.
+  /// Google tokens are checked against the plugin's JWKS; those of other
+  /// providers by the backend, which knows their keys.
+  async fn verify(&self, provider: Option<&str>, id_token: &str, nonce: Option<&str>) -> crate::Result<IdTokenClaims> {
+    match provider {
+      None | Some(GOOGLE) => self.verifier.verify(id_token, nonce).await,
+      Some(provider) => self.backend.verify_id_token(provider, id_token, nonce).await,
+    }
+  }
+
+  /// Signs out a session whose ID token failed verification, then returns `error`.
+  async fn discard(&self, error: Error) -> Error {
+    if let Err(sign_out_error) = self.backend.sign_out().await {
+      log::warn!("Failed to sign out after an ID token was refused: {sign_out_error}");
+    }
+    error
+  }
+
+  /// Applies the account policy to the claims of a new sign-in. A refused
+  /// account is signed out again before the error is returned, so no session
+  /// survives it.
+  async fn enforce_policy(&self, claims: &IdTokenClaims) -> crate::Result<()> {
+    let Err(e) = self.policy.check(claims) else {
+      return Ok(());
+    };
+    let removed = match self.backend.remove_account(&claims.sub).await {
+      Err(Error::UnsupportedPlatform) => self.backend.sign_out().await,
+      removed => removed,
+    };
+    if let Err(remove_error) = removed {
+      log::warn!("Failed to sign out an account refused by the policy: {remove_error}");
+    }
+    let mut profile = self.profile.lock().unwrap();
+    if profile.as_ref().is_some_and(|profile| profile.sub == claims.sub) {
+      *profile = None;
+    }
+    Err(e)
+  }
+
+  fn signed_in(&self, response: &GoogleSignInResponse, claims: IdTokenClaims) {
+    *self.profile.lock().unwrap() = Some(claims.clone());
+    self.emit(
+      AuthState::SignedIn,
+      Some(AccountSummary {
+        email: response.email.clone().or(claims.email.clone()),
+        display_name: response.display_name.clone().or(claims.name.clone()),
+        picture: response.profile_picture_uri.clone().or(claims.picture.clone()),
+        id: claims.sub,
+      }),
+    );
+  }
+
+  /// Asks the signed-in account for additional scopes. Declined scopes are reported
+  /// in [`RequestScopesResponse::denied`] rather than as an error.
+  pub async fn request_scopes(&self, scopes: Vec<String>) -> crate::Result<RequestScopesResponse> {
+    let response = self.interactive(self.backend.request_scopes(scopes)).await?;
+    if !response.granted.is_empty() {
+      self.emit(AuthState::TokenRefreshed, self.backend.account());
+    }
+    Ok(response)
+  }
+
+  /// Aborts every sign-in or scope request in flight; they fail with
+  /// [`Error::Cancelled`]. On desktop this also closes the loopback listener, so a
+  /// late redirect from the browser is refused.
+  ///
+  /// The native account pickers cannot be dismissed from Rust: on mobile the
+  /// attempt is abandoned and whatever the picker reports later is ignored.
+  pub fn cancel_sign_in(&self) {
+    self.cancel.send_modify(|generation| *generation += 1);
+  }
+
+  /// Runs an interactive `attempt`, giving up when it is cancelled or takes
+  /// longer than the sign-in timeout. Giving up drops the attempt, which releases
+  /// whatever it was waiting on.
+  async fn interactive<T>(&self, attempt: impl Future<Output = crate::Result<T>>) -> crate::Result<T> {
+    let mut cancelled = self.cancel.subscribe();
+    tokio::select! {
+      result = attempt => result,
+      _ = cancelled.changed() => Err(Error::Cancelled),
+      _ = tokio::time::sleep(self.sign_in_timeout) => Err(Error::TimedOut),
+    }
+  }
+
+  /// Restores the persisted session, silently refreshing its tokens when they are
+  /// about to expire. Run once by the plugin setup.
+  pub(crate) async fn restore(&self) {
+    let restored = match self.backend.is_signed_in().await {
+      Ok(true) => match self.tokens(false).await {
+        Ok(tokens) => match self.session_claims(&tokens).await {
+          Ok(claims) => {
+            if let Err(e) = self.policy.check(&claims) {
+              // Signed in before the policy was tightened.
+              let _ = self.enforce_policy(&claims).await;
+              RestoredSession {
+                signed_in: false,
+                account: None,
+                refresh_error: Some(e.code()),
+              }
+            } else {
+              let account = AccountSummary::from(&claims);
+              let mut profile = self.profile.lock().unwrap();
+              if profile.is_none() {
+                *profile = Some(claims);
+              }
+              RestoredSession {
+                signed_in: true,
+                account: Some(account),
+                refresh_error: None,
+              }
+            }
+          }
+          // The keys could not be fetched, e.g. when starting offline.
+          Err(e @ Error::Http(_)) => RestoredSession {
+            signed_in: true,
+            account: self.backend.account(),
+            refresh_error: Some(e.code()),
+          },
+          Err(e) => {
+            log::warn!("Dropping a google-auth session whose ID token fails verification: {e}");
+            let e = self.discard(e).await;
+            RestoredSession {
+              signed_in: false,
+              account: None,
+              refresh_error: Some(e.code()),
+            }
+          }
+        },
+        // The session is gone; `tokens` already reported it as expired.
+        Err(e) if ends_session(&e) => RestoredSession {
+          signed_in: false,
+          account: None,
+          refresh_error: Some(e.code()),
+        },
+        Err(e) => {
+          log::warn!("Keeping the google-auth session, tokens could not be refreshed: {e}");
+          RestoredSession {
+            signed_in: true,
+            account: self.backend.account(),
+            refresh_error: Some(e.code()),
+          }
+        }
+      },
+      Ok(false) => RestoredSession {
+        signed_in: false,
+        account: None,
+        refresh_error: None,
+      },
+      Err(e) => {
+        log::warn!("Failed to restore the google-auth session: {e}");
+        RestoredSession {
+          signed_in: false,
+          account: None,
+          refresh_error: Some(e.code()),
+        }
+      }
+    };
+    self.restored.send_replace(Some(restored));
+  }
+
+  /// Waits for the startup restore and returns its outcome, so the app can keep
+  /// its splash screen up until it knows whether someone is signed in.
+  pub async fn restore_session(&self) -> RestoredSession {
+    let mut restored = self.restored.subscribe();
+    loop {
+      if let Some(session) = restored.borrow_and_update().clone() {
+        return session;
+      }
+      // The sender lives as long as `self`, so this only returns on a new value.
+      let _ = restored.changed().await;
+    }
+  }
+
+  /// Whether the startup restore has finished, and its outcome if so.
+  pub fn readiness(&self) -> AuthReadiness {
+    let session = self.restored.borrow().clone();
+    AuthReadiness {
+      ready: session.is_some(),
+      session,
+    }
+  }
+
+  /// Returns the current ID and access tokens, refreshing them first when they are
+  /// about to expire or `force_refresh` is set.
+  pub async fn tokens(&self, force_refresh: bool) -> crate::Result<GetTokensResponse> {
+    let account = self.backend.account();
+    match self.backend.tokens(force_refresh).await {
+      Ok(fetched) => {
+        if fetched.refreshed {
+          let claims = self.session_claims(&fetched.tokens).await?;
+          let account = AccountSummary::from(&claims);
+          *self.profile.lock().unwrap() = Some(claims);
+          self.emit(AuthState::TokenRefreshed, Some(account));
+        }
+        Ok(fetched.tokens)
+      }
+      Err(e) => {
+        // A backend reports a session it had to drop as not signed in.
+        let dropped = matches!(e, Error::NotSignedIn) && account.is_some();
+        if dropped || ends_session(&e) {
+          self.profile.lock().unwrap().take();
+          self.emit(AuthState::Expired, account);
+        }
+        Err(e)
+      }
+    }
+  }
+
+  /// Verified claims of the session `tokens` belong to: those the backend keeps,
+  /// or those of the Google ID token when it keeps none.
+  async fn session_claims(&self, tokens: &GetTokensResponse) -> crate::Result<IdTokenClaims> {
+    match self.backend.claims() {
+      Some(claims) => Ok(claims),
+      None => self.verify(None, &tokens.id_token, None).await,
+    }
+  }
+
+  /// Forgets the active account and its tokens. Other stored accounts stay
+  /// available to [`GoogleAuth::switch_account`].
+  pub async fn google_sign_out(&self) -> crate::Result<()> {
+    let was_signed_in = self.backend.is_signed_in().await.unwrap_or(true);
+    self.backend.sign_out().await?;
+    self.profile.lock().unwrap().take();
+    if was_signed_in {
+      self.emit(AuthState::SignedOut, None);
+    }
+    Ok(())
+  }
+
+  /// Revokes the app's access to the Google account and forgets the session,
+  /// e.g. when the user deletes their account.
+  pub async fn revoke_access(&self) -> crate::Result<()> {
+    self.backend.revoke().await?;
+    self.profile.lock().unwrap().take();
+    self.emit(AuthState::SignedOut, None);
+    Ok(())
+  }
+
+  /// Every account with a stored session and which of them is active.
+  pub fn list_accounts(&self) -> ListAccountsResponse {
+    let mut accounts = self.backend.accounts();
+    let active = self.active_account();
+    if let Some(active) = &active {
+      if !accounts.iter().any(|account| account.id == active.id) {
+        accounts.push(active.clone());
+      }
+    }
+    ListAccountsResponse {
+      accounts,
+      active_account_id: active.map(|account| account.id),
+    }
+  }
+
+  /// Makes another stored account the active one; tokens and the current user
+  /// are then those of that account.
+  pub async fn switch_account(&self, id: &str) -> crate::Result<()> {
+    self.backend.switch_account(id).await?;
+    if let Some(claims) = self.backend.claims() {
+      self.enforce_policy(&claims).await?;
+    }
+    *self.profile.lock().unwrap() = self.backend.claims();
+    self.emit(AuthState::AccountSwitched, self.backend.account());
+    Ok(())
+  }
+
+  /// Forgets a stored account and its tokens. Removing the active account signs
+  /// out; the grant itself is left alone, see [`GoogleAuth::revoke_access`].
+  pub async fn remove_account(&self, id: &str) -> crate::Result<()> {
+    let removed = self.backend.accounts().into_iter().find(|account| account.id == id);
+    self.backend.remove_account(id).await?;
+    if self.backend.account().is_none() {
+      self.profile.lock().unwrap().take();
+    }
+    self.emit(AuthState::AccountRemoved, removed);
+    Ok(())
+  }
+
+  pub async fn is_signed_in(&self) -> crate::Result<IsSignedInResponse> {
+    Ok(IsSignedInResponse {
+      is_signed_in: self.backend.is_signed_in().await?,
+    })
+  }
+
+  /// Emits a state change, along with the account that is active afterwards.
+  fn emit(&self, state: AuthState, account: Option<AccountSummary>) {
+    self.events.emit(state, account, self.active_account());
+  }
+
+  fn active_account(&self) -> Option<AccountSummary> {
+    self
+      .backend
+      .account()
+      .or_else(|| self.profile.lock().unwrap().as_ref().map(AccountSummary::from))
+  }
+
+  /// Returns the profile of the signed-in account from the cached ID token claims,
+  /// without going to the network unless the avatar has not been downloaded yet.
+  pub async fn current_user(&self) -> crate::Result<Option<CurrentUser>> {
+    let Some(claims) = self.backend.claims().or_else(|| self.profile.lock().unwrap().clone()) else {
+      return Ok(None);
+    };
+
+    let mut avatar_path = None;
+    if let (Some(avatars), Some(picture)) = (&self.avatars, claims.picture.as_deref()) {
+      match avatars.get(picture).await {
+        Ok(path) => avatar_path = Some(path),
+        // Offline or a broken picture: the UI falls back to initials.
+        Err(e) => log::warn!("Failed to cache profile picture: {e}"),
+      }
+    }
+    Ok(Some(CurrentUser::new(claims, avatar_path)))
+  }
+
+  /// Verifies an ID token's signature and standard claims against the configured JWKS.
+  pub async fn verify_id_token(&self, id_token: &str, nonce: Option<&str>) -> crate::Result<IdTokenClaims> {
+    self.verifier.verify(id_token, nonce).await
+  }
+}
+
+/// Whether a failed refresh means the session is gone for good.
+fn ends_session(error: &Error) -> bool {
+  match error {
+    Error::OAuth { error, .. } => error == "invalid_grant",
+    Error::Native { code, .. } => matches!(code, ErrorCode::NoAccount | ErrorCode::NotSignedIn),
+    _ => false,
+  }
+}
@@ -0,0 +1,320 @@
+use std::time::{Duration, Instant};
+
+use jsonwebtoken::{jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use reqwest::header::CACHE_CONTROL;
+use tokio::sync::Mutex;
+
+use crate::{models::IdTokenClaims, oauth::unix_now, Error};
+
+pub(crate) const GOOGLE_JWKS_URI: &str = "https://www.googleapis.com/oauth2/v3/certs";
+pub(crate) const GOOGLE_ISSUERS: &[&str] = &["https://accounts.google.com", "accounts.google.com"];
+
+/// Tolerated difference between our clock and the issuer's, in seconds.
+const CLOCK_SKEW: u64 = 300;
+/// Lifetime of a key set served without a usable `Cache-Control: max-age`.
+const DEFAULT_JWKS_TTL: Duration = Duration::from_secs(3600);
+/// Minimum delay between refetches triggered by an unknown `kid`.
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Settings for ID token verification.
+#[derive(Debug, Clone)]
+pub(crate) struct VerifierConfig {
+  pub jwks_uri: String,
+  pub issuers: Vec<String>,
+  /// Accepted `aud` values. When empty, the OAuth client ID is used.
+  pub audiences: Vec<String>,
+}
+
+impl Default for VerifierConfig {
+  fn default() -> Self {
+    Self {
+      jwks_uri: GOOGLE_JWKS_URI.into(),
+      issuers: GOOGLE_ISSUERS.iter().map(|s| s.to_string()).collect(),
+      audiences: Vec::new(),
+    }
+  }
+}
+
+struct CachedJwks {
+  keys: JwkSet,
+  fetched_at: Instant,
+  expires_at: Instant,
+}
+
+/// Verifies RS256 ID tokens against a JWKS that is cached according to its
+/// `Cache-Control` header.
+pub(crate) struct IdTokenVerifier {
+  http: reqwest::Client,
+  config: VerifierConfig,
+  cache: Mutex<Option<CachedJwks>>,
+}
+
+impl IdTokenVerifier {
+  pub fn new(http: reqwest::Client, config: VerifierConfig) -> Self {
+    Self {
+      http,
+      config,
+      cache: Mutex::new(None),
+    }
+  }
+
+  /// Checks the signature, `iss`, `aud`, `exp`, `iat` and, when `expected_nonce`
+  /// is given, `nonce` of `id_token`.
+  pub async fn verify(&self, id_token: &str, expected_nonce: Option<&str>) -> crate::Result<IdTokenClaims> {
+    if self.config.audiences.is_empty() {
+      return Err(Error::MissingConfig("audiences"));
+    }
+
+    let header = jsonwebtoken::decode_header(id_token)?;
+    if header.alg != Algorithm::RS256 {
+      return Err(Error::InvalidIdToken("unexpected signing algorithm"));
+    }
+    let kid = header.kid.ok_or(Error::InvalidIdToken("missing key id"))?;
+    let key = self.decoding_key(&kid).await?;
+
+    let mut validation = Validation::new(Algorithm::RS256);
+    validation.leeway = CLOCK_SKEW;
+    validation.set_issuer(&self.config.issuers);
+    validation.set_audience(&self.config.audiences);
+    validation.set_required_spec_claims(&["exp", "iat", "iss", "aud", "sub"]);
+
+    let claims = jsonwebtoken::decode::<IdTokenClaims>(id_token, &key, &validation)?.claims;
+
+    // `Validation` accepts a token as soon as one of its audiences is ours; with
+    // several, the party it was issued to has to be ours too.
+    if claims.aud.iter().count() > 1 {
+      let azp = claims
+        .azp
+        .as_deref()
+        .ok_or(Error::InvalidIdToken("several audiences but no authorized party"))?;
+      if !self.config.audiences.iter().any(|audience| audience == azp) {
+        return Err(Error::InvalidIdToken("token was issued to another client"));
+      }
+    }
+    if claims.iat > unix_now() + CLOCK_SKEW {
+      return Err(Error::InvalidIdToken("token issued in the future"));
+    }
+    if let Some(expected) = expected_nonce {
+      if claims.nonce.as_deref() != Some(expected) {
+        return Err(Error::NonceMismatch);
+      }
+    }
+
+    Ok(claims)
+  }
+
+  async fn decoding_key(&self, kid: &str) -> crate::Result<DecodingKey> {
+    let mut cache = self.cache.lock().await;
+
+    let stale = cache
+      .as_ref()
+      .map_or(true, |cached| cached.expires_at <= Instant::now());
+    if stale {
+      *cache = Some(self.fetch().await?);
+    }
+
+    if let Some(jwk) = cache.as_ref().and_then(|cached| cached.keys.find(kid)) {
+      return Ok(DecodingKey::from_jwk(jwk)?);
+    }
+
+    // The issuer may have rotated its keys before our copy expired.
+    let recently_fetched = cache
+      .as_ref()
+      .is_some_and(|cached| cached.fetched_at.elapsed() < MIN_REFRESH_INTERVAL);
+    if !recently_fetched {
+      *cache = Some(self.fetch().await?);
+      if let Some(jwk) = cache.as_ref().and_then(|cached| cached.keys.find(kid)) {
+        return Ok(DecodingKey::from_jwk(jwk)?);
+      }
+    }
+
+    Err(Error::InvalidIdToken("unknown key id"))
+  }
+
+  async fn fetch(&self) -> crate::Result<CachedJwks> {
+    let response = self
+      .http
+      .get(&self.config.jwks_uri)
+      .send()
+      .await?
+      .error_for_status()?;
+    let ttl = response
+      .headers()
+      .get(CACHE_CONTROL)
+      .and_then(|value| value.to_str().ok())
+      .and_then(max_age)
+      .unwrap_or(DEFAULT_JWKS_TTL);
+    let keys = response.json::<JwkSet>().await?;
+
+    let fetched_at = Instant::now();
+    Ok(CachedJwks {
+      keys,
+      fetched_at,
+      expires_at: fetched_at + ttl,
+    })
+  }
+}
+
+/// Extracts `max-age` from a `Cache-Control` header value, honouring `no-cache`/`no-store`.
+fn max_age(cache_control: &str) -> Option<Duration> {
+  let mut max_age = None;
+  for directive in cache_control.split(',').map(str::trim) {
+    if directive.eq_ignore_ascii_case("no-cache") || directive.eq_ignore_ascii_case("no-store") {
+      return Some(Duration::ZERO);
+    }
+    if let Some((name, value)) = directive.split_once('=') {
+      if name.trim().eq_ignore_ascii_case("max-age") {
+        max_age = value.trim().trim_matches('"').parse().ok().map(Duration::from_secs);
+      }
+    }
+  }
+  max_age
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::{Arc, Mutex};
+
+  use serde_json::Value;
+
+  use super::*;
+  use crate::test_support::{claims, jwks, keys, Response, StandIn};
+
+  const ISSUER: &str = "https://accounts.google.com";
+  const CLIENT_ID: &str = "test-client.apps.googleusercontent.com";
+
+  /// A verifier for [`CLIENT_ID`] against a stand-in serving `published`, which
+  /// tests may change to rotate keys.
+  async fn setup(published: Value) -> (IdTokenVerifier, StandIn, Arc<Mutex<Value>>) {
+    let published = Arc::new(Mutex::new(published));
+    let served = published.clone();
+    let idp = StandIn::start(move |request| match request.path.as_str() {
+      "/jwks" => Response::json(served.lock().unwrap().clone()).header("Cache-Control", "public, max-age=3600"),
+      _ => Response::not_found(),
+    })
+    .await;
+    let verifier = IdTokenVerifier::new(
+      reqwest::Client::new(),
+      VerifierConfig {
+        jwks_uri: format!("{}/jwks", idp.url),
+        issuers: vec![ISSUER.into()],
+        audiences: vec![CLIENT_ID.into()],
+      },
+    );
+    (verifier, idp, published)
+  }
+
+  fn claims_with(changes: &[(&str, Value)]) -> Value {
+    let mut claims = claims(ISSUER, CLIENT_ID);
+    for (name, value) in changes {
+      claims[*name] = value.clone();
+    }
+    claims
+  }
+
+  fn rejects_as_invalid(result: crate::Result<IdTokenClaims>) -> bool {
+    matches!(result, Err(Error::Jwt(_) | Error::InvalidIdToken(_)))
+  }
+
+  #[tokio::test]
+  async fn accepts_a_good_token() {
+    let [key, ..] = keys();
+    let (verifier, _idp, _) = setup(jwks(&[key])).await;
+    let token = key.sign(&claims_with(&[("nonce", "n-1".into())]));
+
+    let claims = verifier.verify(&token, Some("n-1")).await.unwrap();
+    assert_eq!(claims.sub, "110248495921238986420");
+    assert!(claims.aud.contains(CLIENT_ID));
+    assert_eq!(claims.email.as_deref(), Some("ada@be-out.app"));
+  }
+
+  #[tokio::test]
+  async fn rejects_wrong_audience_or_issuer() {
+    let [key, ..] = keys();
+    let (verifier, _idp, _) = setup(jwks(&[key])).await;
+
+    let token = key.sign(&claims_with(&[("aud", "another-app.apps.googleusercontent.com".into())]));
+    assert!(rejects_as_invalid(verifier.verify(&token, None).await));
+    let token = key.sign(&claims_with(&[("iss", "https://evil.example".into())]));
+    assert!(rejects_as_invalid(verifier.verify(&token, None).await));
+  }
+
+  #[tokio::test]
+  async fn checks_every_audience_of_an_array() {
+    let [key, ..] = keys();
+    let (verifier, _idp, _) = setup(jwks(&[key])).await;
+
+    let token = key.sign(&claims_with(&[
+      ("aud", serde_json::json!(["account", CLIENT_ID])),
+      ("azp", CLIENT_ID.into()),
+    ]));
+    let claims = verifier.verify(&token, None).await.unwrap();
+    assert!(claims.aud.contains("account") && claims.aud.contains(CLIENT_ID));
+
+    let token = key.sign(&claims_with(&[
+      ("aud", serde_json::json!(["account", CLIENT_ID])),
+      ("azp", "account".into()),
+    ]));
+    assert!(rejects_as_invalid(verifier.verify(&token, None).await));
+  }
+
+  #[tokio::test]
+  async fn tolerates_clock_skew_up_to_five_minutes() {
+    let [key, ..] = keys();
+    let (verifier, _idp, _) = setup(jwks(&[key])).await;
+    let now = unix_now();
+
+    let token = key.sign(&claims_with(&[("iat", (now - 3900).into()), ("exp", (now - 200).into())]));
+    assert!(verifier.verify(&token, None).await.is_ok());
+    let token = key.sign(&claims_with(&[("iat", (now - 4000).into()), ("exp", (now - 301).into())]));
+    assert!(rejects_as_invalid(verifier.verify(&token, None).await));
+  }
+
+  #[tokio::test]
+  async fn rejects_a_nonce_of_another_attempt() {
+    let [key, ..] = keys();
+    let (verifier, _idp, _) = setup(jwks(&[key])).await;
+    let token = key.sign(&claims_with(&[("nonce", "n-1".into())]));
+
+    assert!(matches!(verifier.verify(&token, Some("n-2")).await, Err(Error::NonceMismatch)));
+  }
+
+  #[tokio::test]
+  async fn refetches_for_an_unknown_kid_at_most_once_a_minute() {
+    let [first, second, third] = keys();
+    let (verifier, idp, published) = setup(jwks(&[first])).await;
+    verifier.verify(&first.sign(&claims_with(&[])), None).await.unwrap();
+    assert_eq!(idp.hits("/jwks"), 1);
+
+    // The issuer rotates right after our fetch: the new key is not picked up yet.
+    *published.lock().unwrap() = jwks(&[first, second]);
+    let rotated = second.sign(&claims_with(&[]));
+    assert!(matches!(
+      verifier.verify(&rotated, None).await,
+      Err(Error::InvalidIdToken("unknown key id"))
+    ));
+    assert_eq!(idp.hits("/jwks"), 1);
+
+    // A minute later, the unknown kid triggers exactly one refetch.
+    age_cache(&verifier).await;
+    verifier.verify(&rotated, None).await.unwrap();
+    assert_eq!(idp.hits("/jwks"), 2);
+    verifier.verify(&rotated, None).await.unwrap();
+    assert_eq!(idp.hits("/jwks"), 2);
+
+    // A kid the issuer does not publish costs one fetch per minute at most.
+    age_cache(&verifier).await;
+    let unknown = third.sign(&claims_with(&[]));
+    for _ in 0..3 {
+      assert!(verifier.verify(&unknown, None).await.is_err());
+    }
+    assert_eq!(idp.hits("/jwks"), 3);
+  }
+
+  /// Pretends the cached key set was fetched just over a minute ago.
+  async fn age_cache(verifier: &IdTokenVerifier) {
+    let mut cache = verifier.cache.lock().await;
+    let cached = cache.as_mut().unwrap();
+    cached.fetched_at -= MIN_REFRESH_INTERVAL + Duration::from_secs(1);
+  }
+}
@@ -1,25 +1,57 @@
+use std::{ops::RangeInclusive, path::PathBuf, sync::Arc, time::Duration};
+
 use tauri::{
-  plugin::{Builder, TauriPlugin},
+  plugin::{Builder as PluginBuilder, TauriPlugin},
   Manager, Runtime,
 };
 
 pub use models::*;
 
+#[cfg(desktop)]
+mod apple;
 #[cfg(desktop)]
 mod desktop;
+#[cfg(desktop)]
+mod device;
+#[cfg(desktop)]
+mod loopback;
 #[cfg(mobile)]
 mod mobile;
 
+mod avatar;
+mod backend;
 mod commands;
+mod config;
 mod error;
+mod events;
+#[cfg(desktop)]
+mod facebook;
+mod google_auth;
+mod id_token;
+#[cfg(feature = "mock")]
+mod mock;
 mod models;
-
-pub use error::{Error, Result};
-
+mod nonce;
+mod oauth;
 #[cfg(desktop)]
-use desktop::GoogleAuth;
-#[cfg(mobile)]
-use mobile::GoogleAuth;
+mod oidc;
+mod pkce;
+mod policy;
+mod sealed;
+#[cfg(desktop)]
+mod session;
+#[cfg(test)]
+mod test_support;
+
+pub use backend::{AuthBackend, DeviceAuthorization, FetchedTokens};
+pub use config::{AccountPolicy, AppleConfig, Config, FacebookConfig, PortRange, ProviderConfig};
+pub use error::{Error, ErrorCode, Result};
+pub use events::STATE_CHANGED_EVENT;
+pub use google_auth::GoogleAuth;
+pub use pkce::{random_token, Pkce};
+pub use sealed::SealedDir;
+#[cfg(feature = "mock")]
+pub use mock::{MockBackend, MockOutcome};
 
 /// Extensions to [`tauri::App`], [`tauri::AppHandle`] and [`tauri::Window`] to access the google-auth APIs.
 pub trait GoogleAuthExt<R: Runtime> {
@@ -32,22 +64,200 @@ impl<R: Runtime, T: Manager<R>> crate::GoogleAuthExt<R> for T {
   }
 }
 
+type UrlOpener = Arc<dyn Fn(&str) -> std::io::Result<()> + Send + Sync>;
+
+/// Settings collected by the [`Builder`] and handed to the platform implementation.
+#[cfg_attr(mobile, allow(dead_code))]
+pub(crate) struct Options {
+  client: oauth::ClientConfig,
+  verifier: id_token::VerifierConfig,
+  open_url: Option<UrlOpener>,
+  session_dir: Option<PathBuf>,
+  redirect_ports: Option<RangeInclusive<u16>>,
+  sign_in_timeout: Option<Duration>,
+  /// The `tauri.conf.json` configuration the options were resolved from.
+  config: Config,
+}
+
+/// Builder for the google-auth plugin.
+pub struct Builder {
+  options: Options,
+  backend: Option<Box<dyn AuthBackend>>,
+}
+
+impl Default for Builder {
+  fn default() -> Self {
+    Self {
+      backend: None,
+      options: Options {
+        client: oauth::ClientConfig::default(),
+        verifier: id_token::VerifierConfig::default(),
+        open_url: None,
+        session_dir: None,
+        redirect_ports: None,
+        sign_in_timeout: None,
+        config: Config::default(),
+      },
+    }
+  }
+}
+
+impl Builder {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// OAuth client ID used by the desktop browser flow. Overrides `desktopClientId`.
+  pub fn client_id(mut self, client_id: impl Into<String>) -> Self {
+    self.options.client.client_id = Some(client_id.into());
+    self
+  }
+
+  /// Secret of the desktop OAuth client, sent to the token endpoint alongside the PKCE verifier.
+  pub fn client_secret(mut self, client_secret: impl Into<String>) -> Self {
+    self.options.client.client_secret = Some(client_secret.into());
+    self
+  }
+
+  /// Overrides the authorization endpoint. Defaults to Google's.
+  pub fn authorization_endpoint(mut self, url: impl Into<String>) -> Self {
+    self.options.client.authorization_endpoint = url.into();
+    self
+  }
+
+  /// Overrides the token endpoint. Defaults to Google's.
+  pub fn token_endpoint(mut self, url: impl Into<String>) -> Self {
+    self.options.client.token_endpoint = url.into();
+    self
+  }
+
+  /// Overrides the token revocation endpoint. Defaults to Google's.
+  pub fn revocation_endpoint(mut self, url: impl Into<String>) -> Self {
+    self.options.client.revocation_endpoint = Some(url.into());
+    self
+  }
+
+  /// Overrides the device authorization endpoint. Defaults to Google's.
+  pub fn device_authorization_endpoint(mut self, url: impl Into<String>) -> Self {
+    self.options.client.device_authorization_endpoint = Some(url.into());
+    self
+  }
+
+  /// Scopes requested at sign-in. Overrides `scopes`.
+  pub fn scopes<I, S>(mut self, scopes: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    self.options.client.scopes = scopes.into_iter().map(Into::into).collect();
+    self
+  }
+
+  /// Overrides the JWKS used to verify ID token signatures. Defaults to Google's.
+  pub fn jwks_uri(mut self, url: impl Into<String>) -> Self {
+    self.options.verifier.jwks_uri = url.into();
+    self
+  }
+
+  /// Accepted ID token issuers. Defaults to Google's.
+  pub fn issuers<I, S>(mut self, issuers: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    self.options.verifier.issuers = issuers.into_iter().map(Into::into).collect();
+    self
+  }
+
+  /// Client IDs accepted as ID token audience. Defaults to every client ID in the
+  /// plugin configuration.
+  pub fn audiences<I, S>(mut self, audiences: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    self.options.verifier.audiences = audiences.into_iter().map(Into::into).collect();
+    self
+  }
+
+  /// Overrides how the authorization URL is opened. Defaults to the system browser.
+  pub fn open_url_with<F>(mut self, open_url: F) -> Self
+  where
+    F: Fn(&str) -> std::io::Result<()> + Send + Sync + 'static,
+  {
+    self.options.open_url = Some(Arc::new(open_url));
+    self
+  }
+
+  /// Ports the desktop loopback listener may bind to. Overrides `redirectPorts`.
+  pub fn redirect_ports(mut self, ports: RangeInclusive<u16>) -> Self {
+    self.options.redirect_ports = Some(ports);
+    self
+  }
+
+  /// How long an interactive sign-in may take. Overrides `signInTimeoutSecs`.
+  pub fn sign_in_timeout(mut self, timeout: Duration) -> Self {
+    self.options.sign_in_timeout = Some(timeout);
+    self
+  }
+
+  /// Directory holding the encrypted session. Defaults to `google-auth` in the app data dir.
+  pub fn session_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+    self.options.session_dir = Some(dir.into());
+    self
+  }
+
+  /// Replaces the platform backend, e.g. with a `MockBackend` in tests.
+  pub fn backend(mut self, backend: impl AuthBackend) -> Self {
+    self.backend = Some(Box::new(backend));
+    self
+  }
+
+  pub fn build<R: Runtime>(self) -> TauriPlugin<R, Option<Config>> {
+    let Self { options, backend } = self;
+    PluginBuilder::<R, Option<Config>>::new("google-auth")
+      .invoke_handler(tauri::generate_handler![
+        commands::ping,
+        commands::restore_session,
+        commands::get_auth_readiness,
+        commands::google_sign_in,
+        commands::start_device_sign_in,
+        commands::complete_device_sign_in,
+        commands::cancel_sign_in,
+        commands::request_scopes,
+        commands::get_tokens,
+        commands::google_sign_out,
+        commands::list_accounts,
+        commands::switch_account,
+        commands::remove_account,
+        commands::revoke_access,
+        commands::is_signed_in,
+        commands::get_current_user,
+        commands::verify_id_token
+      ])
+      .setup(move |app, api| {
+        let options = options.resolve(api.config().clone().unwrap_or_default())?;
+        let verifier = id_token::IdTokenVerifier::new(reqwest::Client::new(), options.verifier.clone());
+        let timeout = options.sign_in_timeout.unwrap_or_default();
+        let policy = options.config.policy.clone();
+        let backend: Box<dyn AuthBackend> = match backend {
+          Some(backend) => backend,
+          #[cfg(mobile)]
+          None => Box::new(mobile::init(app, api, options)?),
+          #[cfg(desktop)]
+          None => Box::new(desktop::init(app, api, options)?),
+        };
+        app.manage(GoogleAuth::new(app, backend, verifier, timeout, policy));
+
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move { app.google_auth().restore().await });
+        Ok(())
+      })
+      .build()
+  }
+}
+
 /// Initializes the plugin.
-pub fn init<R: Runtime>() -> TauriPlugin<R> {
-  Builder::new("google-auth")
-    .invoke_handler(tauri::generate_handler![
-      commands::ping,
-      commands::google_sign_in,
-      commands::google_sign_out,
-      commands::is_signed_in
-    ])
-    .setup(|app, api| {
-      #[cfg(mobile)]
-      let google_auth = mobile::init(app, api)?;
-      #[cfg(desktop)]
-      let google_auth = desktop::init(app, api)?;
-      app.manage(google_auth);
-      Ok(())
-    })
-    .build()
+pub fn init<R: Runtime>() -> TauriPlugin<R, Option<Config>> {
+  Builder::new().build()
 }
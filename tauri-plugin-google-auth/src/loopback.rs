@@ -0,0 +1,212 @@
+use std::{
+  collections::HashMap,
+  io,
+  ops::RangeInclusive,
+  time::{Duration, Instant},
+};
+
+use tokio::{
+  io::{AsyncReadExt, AsyncWriteExt},
+  net::{TcpListener, TcpStream},
+  sync::mpsc,
+  task::JoinSet,
+};
+
+use crate::Error;
+
+const MAX_REQUEST_HEAD: usize = 8 * 1024;
+/// Largest `form_post` body accepted; Apple's carry an ID token and the user's name.
+const MAX_REQUEST_BODY: usize = 64 * 1024;
+
+const SUCCESS_PAGE: &str = "<!doctype html><html><head><meta charset=\"utf-8\"><title>Be Out</title></head>\
+<body style=\"font-family:sans-serif;text-align:center;padding-top:4em\">\
+<h2>Sign-in complete</h2><p>You can close this window and return to the app.</p></body></html>";
+const REJECTED_PAGE: &str = "<!doctype html><html><head><meta charset=\"utf-8\"><title>Be Out</title></head>\
+<body style=\"font-family:sans-serif;text-align:center;padding-top:4em\">\
+<h2>Sign-in link not recognized</h2><p>This page does not belong to the sign-in in progress.</p></body></html>";
+/// How long a connection may take to send its request. Browsers open spare
+/// connections they may never use, which must not hold up the redirect.
+pub(crate) const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Parameters delivered to the redirect URI by the authorization server, either
+/// in the query or, with `response_mode=form_post`, in the request body.
+#[derive(Debug, Default)]
+pub(crate) struct CallbackParams {
+  pub code: Option<String>,
+  pub error: Option<String>,
+  pub error_description: Option<String>,
+  /// Space separated scopes the user granted, as reported by Google.
+  pub scope: Option<String>,
+  /// ID token returned directly by the authorization endpoint (hybrid flow).
+  pub id_token: Option<String>,
+  /// JSON profile Apple posts on the first authorization only.
+  pub user: Option<String>,
+}
+
+/// Ephemeral HTTP listener on 127.0.0.1 that receives a single OAuth redirect.
+pub(crate) struct LoopbackServer {
+  listener: TcpListener,
+  port: u16,
+}
+
+impl LoopbackServer {
+  /// Binds to the first free port in `ports`, or to any free port when `None`.
+  pub async fn bind(ports: Option<RangeInclusive<u16>>) -> crate::Result<Self> {
+    let listener = match ports {
+      None => TcpListener::bind(("127.0.0.1", 0)).await?,
+      Some(ports) => {
+        let mut bound = None;
+        for port in ports.clone() {
+          if let Ok(listener) = TcpListener::bind(("127.0.0.1", port)).await {
+            bound = Some(listener);
+            break;
+          }
+        }
+        bound.ok_or_else(|| {
+          io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("no free redirect port in {}-{}", ports.start(), ports.end()),
+          )
+        })?
+      }
+    };
+    let port = listener.local_addr()?.port();
+    Ok(Self { listener, port })
+  }
+
+  pub fn port(&self) -> u16 {
+    self.port
+  }
+
+  pub fn redirect_uri(&self) -> String {
+    format!("http://127.0.0.1:{}", self.port)
+  }
+
+  /// Waits for the browser to hit the redirect URI with `state` and returns its
+  /// parameters, or fails with [`Error::TimedOut`] at `deadline`.
+  ///
+  /// Connections are served concurrently. Requests for any path other than `/`
+  /// (favicons, probes) are answered with a 404, and requests to `/` without the
+  /// expected `state` with an error page; neither ends the wait, so no other
+  /// local process can abort the sign-in.
+  pub async fn wait_for_callback(self, state: &str, deadline: Instant) -> crate::Result<CallbackParams> {
+    let (sender, mut callbacks) = mpsc::channel(1);
+    // Dropped with the server, which aborts the connections still being served.
+    let mut connections = JoinSet::new();
+    let deadline = tokio::time::Instant::from_std(deadline);
+    loop {
+      tokio::select! {
+        accepted = self.listener.accept() => {
+          let (stream, _) = accepted?;
+          connections.spawn(serve(stream, state.to_string(), sender.clone()));
+        }
+        Some(callback) = callbacks.recv() => return Ok(callback),
+        // Reap finished connections so the set does not grow with every probe.
+        Some(_) = connections.join_next(), if !connections.is_empty() => {}
+        _ = tokio::time::sleep_until(deadline) => return Err(Error::TimedOut),
+      }
+    }
+  }
+}
+
+/// Answers a single connection, passing its parameters to `callbacks` when it is
+/// the redirect carrying `state`.
+async fn serve(mut stream: TcpStream, state: String, callbacks: mpsc::Sender<CallbackParams>) {
+  let request = match tokio::time::timeout(REQUEST_TIMEOUT, read_request(&mut stream)).await {
+    Ok(Ok(Some(request))) => request,
+    Ok(Ok(None) | Err(_)) => {
+      let _ = respond(&mut stream, "400 Bad Request", "").await;
+      return;
+    }
+    // An idle connection: drop it without an answer.
+    Err(_) => return,
+  };
+
+  let (path, query) = request.target.split_once('?').unwrap_or((request.target.as_str(), ""));
+  if path != "/" {
+    let _ = respond(&mut stream, "404 Not Found", "").await;
+    return;
+  }
+
+  let mut pairs: HashMap<String, String> = url::form_urlencoded::parse(query.as_bytes())
+    .chain(url::form_urlencoded::parse(&request.body))
+    .map(|(name, value)| (name.into_owned(), value.into_owned()))
+    .collect();
+  if pairs.get("state") != Some(&state) {
+    let _ = respond(&mut stream, "400 Bad Request", REJECTED_PAGE).await;
+    return;
+  }
+  let _ = respond(&mut stream, "200 OK", SUCCESS_PAGE).await;
+
+  // Only fails once the wait is over, when a late redirect no longer matters.
+  let _ = callbacks
+    .send(CallbackParams {
+      code: pairs.remove("code"),
+      error: pairs.remove("error"),
+      error_description: pairs.remove("error_description"),
+      scope: pairs.remove("scope"),
+      id_token: pairs.remove("id_token"),
+      user: pairs.remove("user"),
+    })
+    .await;
+}
+
+struct Request {
+  target: String,
+  /// Form encoded body of a `POST`, empty for a `GET`.
+  body: Vec<u8>,
+}
+
+/// Reads a `GET` request, or a form `POST` together with its body.
+async fn read_request(stream: &mut TcpStream) -> std::io::Result<Option<Request>> {
+  let mut data = Vec::with_capacity(1024);
+  let mut buf = [0u8; 1024];
+  let head_end = loop {
+    if let Some(pos) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+      break pos + 4;
+    }
+    let n = stream.read(&mut buf).await?;
+    if n == 0 || data.len() + n > MAX_REQUEST_HEAD {
+      return Ok(None);
+    }
+    data.extend_from_slice(&buf[..n]);
+  };
+
+  let head = String::from_utf8_lossy(&data[..head_end]).into_owned();
+  let mut lines = head.lines();
+  let mut parts = lines.next().unwrap_or_default().split_whitespace();
+  let (method, target) = match (parts.next(), parts.next()) {
+    (Some(method @ ("GET" | "POST")), Some(target)) => (method, target.to_string()),
+    _ => return Ok(None),
+  };
+  if method == "GET" {
+    return Ok(Some(Request { target, body: Vec::new() }));
+  }
+
+  let content_length = lines
+    .filter_map(|line| line.split_once(':'))
+    .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+    .and_then(|(_, value)| value.trim().parse::<usize>().ok());
+  let Some(content_length) = content_length.filter(|len| *len <= MAX_REQUEST_BODY) else {
+    return Ok(None);
+  };
+  let mut body = data.split_off(head_end);
+  while body.len() < content_length {
+    let n = stream.read(&mut buf).await?;
+    if n == 0 {
+      return Ok(None);
+    }
+    body.extend_from_slice(&buf[..n]);
+  }
+  body.truncate(content_length);
+  Ok(Some(Request { target, body }))
+}
+
+async fn respond(stream: &mut TcpStream, status: &str, body: &str) -> std::io::Result<()> {
+  let response = format!(
+    "HTTP/1.1 {status}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+    body.len()
+  );
+  stream.write_all(response.as_bytes()).await?;
+  stream.shutdown().await
+}
@@ -1,9 +1,18 @@
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
 use tauri::{
-  plugin::{PluginApi, PluginHandle},
+  plugin::{mobile::PluginInvokeError, PluginApi, PluginHandle},
   AppHandle, Runtime,
 };
 
-use crate::models::*;
+use crate::{
+  backend::{AuthBackend, FetchedTokens},
+  models::*,
+  Config, Error, ErrorCode,
+};
 
 #[cfg(target_os = "ios")]
 tauri::ios_plugin_binding!(init_plugin_google_auth);
@@ -11,8 +20,9 @@ tauri::ios_plugin_binding!(init_plugin_google_auth);
 // initializes the Kotlin or Swift plugin classes
 pub fn init<R: Runtime>(
   _app: &AppHandle<R>,
-  api: PluginApi<R, ()>,
-) -> crate::Result<GoogleAuth<R>> {
+  api: PluginApi<R, Option<crate::Config>>,
+  options: crate::Options,
+) -> crate::Result<MobileBackend<R>> {
   log::info!("Initializing Google Auth mobile plugin...");
 
   #[cfg(target_os = "android")]
@@ -24,7 +34,7 @@ pub fn init<R: Runtime>(
         e
       })?;
     log::info!("Android Google Auth plugin registered successfully");
-    return Ok(GoogleAuth(handle));
+    return Ok(MobileBackend::new(handle, options));
   }
 
   #[cfg(target_os = "ios")]
@@ -36,7 +46,7 @@ pub fn init<R: Runtime>(
         e
       })?;
     log::info!("iOS Google Auth plugin registered successfully");
-    return Ok(GoogleAuth(handle));
+    return Ok(MobileBackend::new(handle, options));
   }
 
   #[cfg(not(any(target_os = "android", target_os = "ios")))]
@@ -46,35 +56,170 @@ pub fn init<R: Runtime>(
   }
 }
 
-/// Access to the google-auth APIs.
-pub struct GoogleAuth<R: Runtime>(PluginHandle<R>);
+/// Sign-in through the Google SDKs of the Kotlin and Swift plugins.
+pub(crate) struct MobileBackend<R: Runtime> {
+  handle: PluginHandle<R>,
+  config: Config,
+  /// The ID token last handed out, to tell whether the SDK issued a new one.
+  id_token: Mutex<Option<String>>,
+}
 
-impl<R: Runtime> GoogleAuth<R> {
-  pub fn ping(&self, payload: PingRequest) -> crate::Result<PingResponse> {
-    self
-      .0
-      .run_mobile_plugin("ping", payload)
-      .map_err(Into::into)
+/// Arguments of the native `google_sign_in` command.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NativeSignInArgs<'a> {
+  #[serde(flatten)]
+  request: GoogleSignInRequest,
+  web_client_id: Option<&'a str>,
+}
+
+/// Arguments of the native `get_tokens` command.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NativeTokensArgs<'a> {
+  force_refresh: bool,
+  web_client_id: Option<&'a str>,
+}
+
+/// Tokens returned by the native `get_tokens` command.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NativeTokens {
+  id_token: String,
+  /// Only the iOS SDK exposes the access token.
+  access_token: Option<String>,
+}
+
+impl<R: Runtime> MobileBackend<R> {
+  fn new(handle: PluginHandle<R>, options: crate::Options) -> Self {
+    Self {
+      handle,
+      config: options.config,
+      id_token: Mutex::new(None),
+    }
   }
 
-  pub fn google_sign_in(&self, payload: GoogleSignInRequest) -> crate::Result<GoogleSignInResponse> {
-    self
-      .0
-      .run_mobile_plugin("google_sign_in", payload)
-      .map_err(Into::into)
+  /// Runs a native command, turning failures the Kotlin and Swift code report
+  /// in-band (`success: false`, `error`, `errorCode`) into [`Error::Native`].
+  ///
+  /// The call runs on its own task: Tauri panics when a native response arrives
+  /// after the awaiting future was dropped, which is exactly what happens when a
+  /// sign-in is cancelled or times out while the account picker is still open.
+  async fn run_native<T: DeserializeOwned>(&self, command: &str, payload: impl Serialize) -> crate::Result<T> {
+    let payload = serde_json::to_value(payload).map_err(PluginInvokeError::CannotSerializePayload)?;
+    let handle = self.handle.clone();
+    let name = command.to_string();
+    let value: Value =
+      tauri::async_runtime::spawn(async move { handle.run_mobile_plugin_async(name, payload).await }).await??;
+    let failed = value.get("success").and_then(Value::as_bool) == Some(false)
+      || value.get("error").is_some_and(|error| !error.is_null());
+    if failed {
+      return Err(Error::Native {
+        code: ErrorCode::from_native(value.get("errorCode").and_then(Value::as_str)),
+        message: value
+          .get("error")
+          .and_then(Value::as_str)
+          .unwrap_or("native plugin call failed")
+          .to_string(),
+      });
+    }
+
+    serde_json::from_value(value).map_err(|e| Error::Native {
+      code: ErrorCode::Internal,
+      message: format!("unexpected response from native `{command}`: {e}"),
+    })
   }
 
-  pub fn google_sign_out(&self) -> crate::Result<GoogleSignOutResponse> {
-    self
-      .0
-      .run_mobile_plugin("google_sign_out", ())
-      .map_err(Into::into)
+  async fn native_sign_in(&self, mut request: GoogleSignInRequest) -> crate::Result<GoogleSignInResponse> {
+    if request.server_client_id.is_none() {
+      request.server_client_id = self.config.server_client_id.clone();
+    }
+    let response: GoogleSignInResponse = self.run_native(
+      "google_sign_in",
+      NativeSignInArgs {
+        request,
+        web_client_id: self.config.web_client_id.as_deref(),
+      },
+    )
+    .await?;
+    *self.id_token.lock().unwrap() = response.id_token.clone();
+    Ok(response)
+  }
+}
+
+#[async_trait]
+impl<R: Runtime> AuthBackend for MobileBackend<R> {
+  async fn ping(&self, payload: PingRequest) -> crate::Result<PingResponse> {
+    self.run_native("ping", payload).await
+  }
+
+  async fn sign_in(&self, request: GoogleSignInRequest) -> crate::Result<GoogleSignInResponse> {
+    if request.provider.as_deref().is_some_and(|provider| provider != "google") {
+      return Err(Error::UnsupportedPlatform);
+    }
+    self.native_sign_in(request).await
+  }
+
+  /// Asks the signed-in account for additional scopes through the platform SDK.
+  async fn request_scopes(&self, scopes: Vec<String>) -> crate::Result<RequestScopesResponse> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct NativeScopes {
+      #[serde(default)]
+      granted_scopes: Vec<String>,
+    }
+
+    let NativeScopes { granted_scopes } = self.run_native(
+      "request_scopes",
+      RequestScopesRequest {
+        scopes: scopes.clone(),
+      },
+    )
+    .await?;
+    let (granted, denied) = scopes
+      .into_iter()
+      .partition(|scope| crate::oauth::scope_granted(&granted_scopes, scope));
+    Ok(RequestScopesResponse { granted, denied })
+  }
+
+  /// Returns the ID token the platform SDK holds for the signed-in account. The
+  /// SDKs renew it shortly before it expires, or right away with `force_refresh`.
+  async fn tokens(&self, force_refresh: bool) -> crate::Result<FetchedTokens> {
+    let NativeTokens { id_token, access_token } = self.run_native(
+      "get_tokens",
+      NativeTokensArgs {
+        force_refresh,
+        web_client_id: self.config.web_client_id.as_deref(),
+      },
+    )
+    .await?;
+    let previous = self.id_token.lock().unwrap().replace(id_token.clone());
+    Ok(FetchedTokens {
+      refreshed: previous.as_deref() != Some(id_token.as_str()),
+      tokens: GetTokensResponse {
+        id_token,
+        access_token,
+        expires_at: None,
+      },
+    })
+  }
+
+  async fn sign_out(&self) -> crate::Result<()> {
+    self.run_native::<Value>("google_sign_out", ()).await?;
+    self.id_token.lock().unwrap().take();
+    Ok(())
+  }
+
+  async fn revoke(&self) -> crate::Result<()> {
+    self.run_native::<Value>("revoke_access", ()).await?;
+    self.id_token.lock().unwrap().take();
+    Ok(())
   }
 
-  pub fn is_signed_in(&self) -> crate::Result<IsSignedInResponse> {
+  async fn is_signed_in(&self) -> crate::Result<bool> {
     self
-      .0
-      .run_mobile_plugin("is_signed_in", ())
-      .map_err(Into::into)
+      .run_native::<IsSignedInResponse>("is_signed_in", ())
+      .await
+      .map(|response| response.is_signed_in)
   }
 }
@@ -0,0 +1,429 @@
+use std::{
+  collections::VecDeque,
+  sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use serde_json::{json, Value};
+
+use crate::{
+  backend::{AuthBackend, FetchedTokens},
+  models::*,
+  oauth::{scope_granted, unix_now},
+  Error, ErrorCode,
+};
+
+const DEFAULT_SCOPES: &[&str] = &["openid", "email", "profile"];
+
+/// Scripted result of the next interactive call or forced refresh.
+#[derive(Debug)]
+pub enum MockOutcome {
+  /// Succeeds for an account with these claims. `nonce`, `iat` and `exp` are
+  /// filled in by the mock.
+  Success(Box<IdTokenClaims>),
+  Cancelled,
+  NetworkError,
+  Error(Error),
+  /// Never completes, like a user who walks away from the account picker.
+  Pending,
+}
+
+/// In-process [`AuthBackend`] for tests and demos, enabled with the `mock` feature.
+///
+/// Each sign-in, scope request and forced token refresh consumes the next queued
+/// [`MockOutcome`]. Clones share their state, so a test can keep one and push
+/// outcomes after handing the other to [`crate::Builder::backend`].
+///
+/// The plugin verifies every ID token before signing in, so give the mock an RSA
+/// key with [`MockBackend::signing_key`] and publish its public half at the
+/// [`crate::Builder::jwks_uri`]. Without one, ID tokens are unsigned and every
+/// sign-in is refused.
+#[derive(Clone, Default)]
+pub struct MockBackend {
+  inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+  outcomes: Mutex<VecDeque<MockOutcome>>,
+  session: Mutex<Option<MockSession>>,
+  signing_key: Mutex<Option<(String, EncodingKey)>>,
+}
+
+#[derive(Clone)]
+struct MockSession {
+  claims: IdTokenClaims,
+  scopes: Vec<String>,
+}
+
+impl MockBackend {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Starts out signed in as `claims`, as if a session had been restored.
+  pub fn signed_in_as(self, claims: IdTokenClaims) -> Self {
+    *self.inner.session.lock().unwrap() = Some(MockSession::new(claims, None, Vec::new()));
+    self
+  }
+
+  /// Signs ID tokens with the RSA `key`, naming it `kid` in their header.
+  pub fn signing_key(self, kid: impl Into<String>, key: EncodingKey) -> Self {
+    *self.inner.signing_key.lock().unwrap() = Some((kid.into(), key));
+    self
+  }
+
+  /// Queues the outcome of the next sign-in, scope request or forced refresh.
+  pub fn push(&self, outcome: MockOutcome) {
+    self.inner.outcomes.lock().unwrap().push_back(outcome);
+  }
+
+  fn next_outcome(&self) -> Option<MockOutcome> {
+    self.inner.outcomes.lock().unwrap().pop_front()
+  }
+
+  fn session(&self) -> Option<MockSession> {
+    self.inner.session.lock().unwrap().clone()
+  }
+
+  fn set_session(&self, session: Option<MockSession>) {
+    *self.inner.session.lock().unwrap() = session;
+  }
+
+  /// Encodes `claims` as an ID token, signed when the mock has a key.
+  fn id_token(&self, claims: &IdTokenClaims) -> crate::Result<String> {
+    let payload = jwt_claims(claims);
+    match &*self.inner.signing_key.lock().unwrap() {
+      Some((kid, key)) => {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(kid.clone());
+        Ok(jsonwebtoken::encode(&header, &payload, key)?)
+      }
+      None => {
+        let header = URL_SAFE_NO_PAD.encode(br#"{"alg":"none","typ":"JWT"}"#);
+        let payload = URL_SAFE_NO_PAD.encode(payload.to_string());
+        Ok(format!("{header}.{payload}."))
+      }
+    }
+  }
+
+  fn tokens_of(&self, session: &MockSession) -> crate::Result<GetTokensResponse> {
+    Ok(GetTokensResponse {
+      id_token: self.id_token(&session.claims)?,
+      access_token: Some(format!("mock-access-token-{}", session.claims.iat)),
+      expires_at: Some(session.claims.exp),
+    })
+  }
+}
+
+impl MockSession {
+  fn new(mut claims: IdTokenClaims, nonce: Option<String>, mut scopes: Vec<String>) -> Self {
+    let now = unix_now();
+    claims.nonce = nonce;
+    claims.iat = now;
+    claims.exp = now + 3600;
+    if scopes.is_empty() {
+      scopes = DEFAULT_SCOPES.iter().map(|s| s.to_string()).collect();
+    }
+    Self { claims, scopes }
+  }
+}
+
+#[async_trait]
+impl AuthBackend for MockBackend {
+  async fn sign_in(&self, request: GoogleSignInRequest) -> crate::Result<GoogleSignInResponse> {
+    let claims = match self.next_outcome() {
+      Some(MockOutcome::Success(claims)) => *claims,
+      Some(other) => return Err(failure(other).await),
+      None => {
+        return Err(Error::Native {
+          code: ErrorCode::NoAccount,
+          message: "MockBackend has no scripted sign-in outcome".into(),
+        })
+      }
+    };
+
+    let session = MockSession::new(claims, request.nonce, Vec::new());
+    let id_token = self.id_token(&session.claims)?;
+    self.set_session(Some(session.clone()));
+    let MockSession { claims, scopes } = session;
+    Ok(GoogleSignInResponse {
+      id_token: Some(id_token),
+      display_name: claims.name,
+      given_name: claims.given_name,
+      family_name: claims.family_name,
+      profile_picture_uri: claims.picture,
+      email: claims.email,
+      server_auth_code: None,
+      code_verifier: None,
+      redirect_uri: None,
+      granted_scopes: scopes,
+    })
+  }
+
+  /// Grants every requested scope unless a failure is queued.
+  async fn request_scopes(&self, scopes: Vec<String>) -> crate::Result<RequestScopesResponse> {
+    let mut session = self.session().ok_or(Error::NotSignedIn)?;
+    match self.next_outcome() {
+      None | Some(MockOutcome::Success(_)) => {}
+      Some(other) => return Err(failure(other).await),
+    }
+
+    for scope in &scopes {
+      if !scope_granted(&session.scopes, scope) {
+        session.scopes.push(scope.clone());
+      }
+    }
+    self.set_session(Some(session));
+    Ok(RequestScopesResponse {
+      granted: scopes,
+      denied: Vec::new(),
+    })
+  }
+
+  async fn tokens(&self, force_refresh: bool) -> crate::Result<FetchedTokens> {
+    let session = self.session().ok_or(Error::NotSignedIn)?;
+    if !force_refresh {
+      return Ok(FetchedTokens {
+        tokens: self.tokens_of(&session)?,
+        refreshed: false,
+      });
+    }
+
+    let claims = match self.next_outcome() {
+      None => session.claims,
+      Some(MockOutcome::Success(claims)) => *claims,
+      Some(other) => {
+        let error = failure(other).await;
+        if matches!(&error, Error::OAuth { error, .. } if error == "invalid_grant") {
+          self.set_session(None);
+        }
+        return Err(error);
+      }
+    };
+    let refreshed = MockSession::new(claims, None, session.scopes);
+    self.set_session(Some(refreshed.clone()));
+    Ok(FetchedTokens {
+      tokens: self.tokens_of(&refreshed)?,
+      refreshed: true,
+    })
+  }
+
+  async fn sign_out(&self) -> crate::Result<()> {
+    self.set_session(None);
+    Ok(())
+  }
+
+  /// Fails with a queued failure outcome, if any, and forgets the session otherwise.
+  async fn revoke(&self) -> crate::Result<()> {
+    self.session().ok_or(Error::NotSignedIn)?;
+    match self.next_outcome() {
+      None | Some(MockOutcome::Success(_)) => {}
+      Some(other) => return Err(failure(other).await),
+    }
+    self.set_session(None);
+    Ok(())
+  }
+
+  async fn is_signed_in(&self) -> crate::Result<bool> {
+    Ok(self.session().is_some())
+  }
+
+  fn claims(&self) -> Option<IdTokenClaims> {
+    self.session().map(|session| session.claims)
+  }
+}
+
+async fn failure(outcome: MockOutcome) -> Error {
+  match outcome {
+    MockOutcome::Pending => std::future::pending().await,
+    MockOutcome::Cancelled => Error::Cancelled,
+    MockOutcome::NetworkError => Error::Native {
+      code: ErrorCode::Network,
+      message: "simulated network error".into(),
+    },
+    MockOutcome::Error(error) => error,
+    MockOutcome::Success(_) => unreachable!("success is not a failure"),
+  }
+}
+
+/// The JWT payload for `claims`.
+fn jwt_claims(claims: &IdTokenClaims) -> Value {
+  // `IdTokenClaims` serializes in camelCase for the webview; JWTs use the
+  // registered snake_case claim names.
+  json!({
+    "iss": claims.iss,
+    "sub": claims.sub,
+    "aud": claims.aud,
+    "azp": claims.azp,
+    "exp": claims.exp,
+    "iat": claims.iat,
+    "email": claims.email,
+    "email_verified": claims.email_verified,
+    "hd": claims.hd,
+    "name": claims.name,
+    "given_name": claims.given_name,
+    "family_name": claims.family_name,
+    "picture": claims.picture,
+    "locale": claims.locale,
+    "nonce": claims.nonce,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use serde_json::{json, Value};
+  use tauri::{
+    test::{mock_builder, mock_context, noop_assets, MockRuntime},
+    App,
+  };
+  use tokio::sync::broadcast::error::TryRecvError;
+
+  use super::*;
+  use crate::{
+    test_support::{claims, jwks, keys, Response, StandIn},
+    Builder, GoogleAuthExt,
+  };
+
+  const ISSUER: &str = "https://accounts.google.com";
+  const CLIENT_ID: &str = "web.apps.googleusercontent.com";
+
+  /// Serves the public half of `keys()[0]` at `/jwks`.
+  async fn jwks_server() -> StandIn {
+    StandIn::start(|request| match request.path.as_str() {
+      "/jwks" => Response::json(jwks(&[&keys()[0]])),
+      _ => Response::not_found(),
+    })
+    .await
+  }
+
+  /// An app running the plugin on `backend`, configured with `config` in
+  /// `tauri.conf.json`, once its startup restore has finished.
+  async fn app(backend: MockBackend, idp: &StandIn, config: Value) -> App<MockRuntime> {
+    let mut context = mock_context(noop_assets());
+    context.config_mut().plugins.0.insert("google-auth".into(), config);
+    let app = mock_builder()
+      .plugin(
+        Builder::new()
+          .backend(backend)
+          .jwks_uri(format!("{}/jwks", idp.url))
+          .issuers([ISSUER])
+          .audiences([CLIENT_ID])
+          .sign_in_timeout(Duration::from_millis(300))
+          .build(),
+      )
+      .build(context)
+      .unwrap();
+    app.google_auth().restore_session().await;
+    app
+  }
+
+  fn backend() -> MockBackend {
+    MockBackend::new().signing_key(keys()[0].kid, keys()[0].encoding_key())
+  }
+
+  fn account(claims: Value) -> MockOutcome {
+    MockOutcome::Success(Box::new(serde_json::from_value(claims).unwrap()))
+  }
+
+  fn request() -> GoogleSignInRequest {
+    serde_json::from_value(json!({})).unwrap()
+  }
+
+  #[tokio::test]
+  async fn signs_in_and_announces_it() {
+    let idp = jwks_server().await;
+    let backend = backend();
+    let app = app(backend.clone(), &idp, json!({})).await;
+    let mut events = app.google_auth().subscribe();
+
+    backend.push(account(claims(ISSUER, CLIENT_ID)));
+    let response = app.google_auth().google_sign_in(request()).await.unwrap();
+    assert_eq!(response.email.as_deref(), Some("ada@be-out.app"));
+    assert!(app.google_auth().is_signed_in().await.unwrap().is_signed_in);
+
+    let change = events.try_recv().unwrap();
+    assert!(matches!(change.state, AuthState::SignedIn));
+    assert_eq!(change.account.unwrap().email.as_deref(), Some("ada@be-out.app"));
+    assert_eq!(change.active_account.unwrap().id, "110248495921238986420");
+    assert!(matches!(events.try_recv(), Err(TryRecvError::Empty)));
+  }
+
+  #[tokio::test]
+  async fn gives_up_on_a_cancelled_sign_in() {
+    let idp = jwks_server().await;
+    let backend = backend();
+    let app = app(backend.clone(), &idp, json!({})).await;
+    let mut events = app.google_auth().subscribe();
+
+    // Cancelled by the user in the account picker.
+    backend.push(MockOutcome::Cancelled);
+    let result = app.google_auth().google_sign_in(request()).await;
+    assert!(matches!(result, Err(Error::Cancelled)));
+
+    // Cancelled by the app while the picker is still open.
+    backend.push(MockOutcome::Pending);
+    let handle = app.handle().clone();
+    let attempt = tokio::spawn(async move { handle.google_auth().google_sign_in(request()).await });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    app.google_auth().cancel_sign_in();
+    assert!(matches!(attempt.await.unwrap(), Err(Error::Cancelled)));
+
+    assert!(!app.google_auth().is_signed_in().await.unwrap().is_signed_in);
+    assert!(matches!(events.try_recv(), Err(TryRecvError::Empty)));
+  }
+
+  #[tokio::test]
+  async fn times_out_when_the_user_walks_away() {
+    let idp = jwks_server().await;
+    let backend = backend();
+    let app = app(backend.clone(), &idp, json!({})).await;
+    let mut events = app.google_auth().subscribe();
+
+    backend.push(MockOutcome::Pending);
+    let result = app.google_auth().google_sign_in(request()).await;
+    assert!(matches!(result, Err(Error::TimedOut)));
+    assert!(matches!(events.try_recv(), Err(TryRecvError::Empty)));
+  }
+
+  #[tokio::test]
+  async fn refuses_accounts_outside_the_policy() {
+    let idp = jwks_server().await;
+    let backend = backend();
+    let config = json!({ "policy": { "allowedHostedDomains": ["be-out.app"] } });
+    let app = app(backend.clone(), &idp, config).await;
+    let mut events = app.google_auth().subscribe();
+
+    let mut outsider = claims(ISSUER, CLIENT_ID);
+    outsider["hd"] = json!("example.org");
+    backend.push(account(outsider));
+    let result = app.google_auth().google_sign_in(request()).await;
+    assert!(matches!(result, Err(Error::PolicyViolation("hosted_domain"))));
+    assert!(!app.google_auth().is_signed_in().await.unwrap().is_signed_in);
+    assert!(app.google_auth().current_user().await.unwrap().is_none());
+    assert!(matches!(events.try_recv(), Err(TryRecvError::Empty)));
+
+    backend.push(account(claims(ISSUER, CLIENT_ID)));
+    app.google_auth().google_sign_in(request()).await.unwrap();
+    assert!(matches!(events.try_recv().unwrap().state, AuthState::SignedIn));
+  }
+
+  #[tokio::test]
+  async fn refuses_tokens_that_fail_verification() {
+    let idp = jwks_server().await;
+    // Signed with a key the JWKS does not publish.
+    let backend = MockBackend::new().signing_key(keys()[1].kid, keys()[1].encoding_key());
+    let app = app(backend.clone(), &idp, json!({})).await;
+    let mut events = app.google_auth().subscribe();
+
+    backend.push(account(claims(ISSUER, CLIENT_ID)));
+    let result = app.google_auth().google_sign_in(request()).await;
+    assert!(matches!(result, Err(Error::InvalidIdToken(_))));
+    assert!(!app.google_auth().is_signed_in().await.unwrap().is_signed_in);
+    assert!(matches!(events.try_recv(), Err(TryRecvError::Empty)));
+  }
+}
@@ -1,5 +1,9 @@
+use std::path::PathBuf;
+
 use serde::{Deserialize, Serialize};
 
+use crate::ErrorCode;
+
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PingRequest {
@@ -15,34 +19,323 @@ pub struct PingResponse {
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GoogleSignInRequest {
+  /// Identity provider to sign in with, as named in the plugin configuration.
+  /// Defaults to `google`; other providers are desktop only.
+  pub provider: Option<String>,
   pub filter_by_authorized_accounts: Option<bool>,
   pub auto_select_enabled: Option<bool>,
+  /// Nonce to bind the ID token to, for backends that issue their own. The plugin
+  /// generates a random one when absent. A nonce is never accepted twice.
   pub nonce: Option<String>,
+  /// OAuth client the server auth code is issued to. Defaults to `serverClientId`.
+  pub server_client_id: Option<String>,
+  /// Also return a one-time code the backend can redeem for its own refresh token.
+  pub request_server_auth_code: Option<bool>,
+  /// Re-prompt for consent so the server auth code always yields a refresh token.
+  pub force_code_for_refresh_token: Option<bool>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GoogleSignInResponse {
-  pub success: bool,
+  /// Absent on desktop when only a server auth code was requested: the code is
+  /// left for the backend to redeem.
   pub id_token: Option<String>,
   pub display_name: Option<String>,
   pub given_name: Option<String>,
   pub family_name: Option<String>,
   pub profile_picture_uri: Option<String>,
   pub email: Option<String>,
-  pub error: Option<String>,
+  pub server_auth_code: Option<String>,
+  /// PKCE verifier the backend must send when redeeming a desktop `server_auth_code`.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub code_verifier: Option<String>,
+  /// Redirect URI the desktop `server_auth_code` was issued for.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub redirect_uri: Option<String>,
+  #[serde(default)]
+  pub granted_scopes: Vec<String>,
+}
+
+/// Claims carried by a Google ID token.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub struct IdTokenClaims {
+  pub iss: String,
+  pub sub: String,
+  pub aud: Audience,
+  /// Client the token was issued to, sent by some providers along several audiences.
+  #[serde(default)]
+  pub azp: Option<String>,
+  pub exp: u64,
+  pub iat: u64,
+  #[serde(default)]
+  pub email: Option<String>,
+  /// Apple sends this as the string `"true"` or `"false"`.
+  #[serde(default, deserialize_with = "bool_or_string")]
+  pub email_verified: Option<bool>,
+  /// Google Workspace domain of the account, absent for consumer accounts.
+  #[serde(default)]
+  pub hd: Option<String>,
+  #[serde(default)]
+  pub name: Option<String>,
+  #[serde(default)]
+  pub given_name: Option<String>,
+  #[serde(default)]
+  pub family_name: Option<String>,
+  #[serde(default)]
+  pub picture: Option<String>,
+  #[serde(default)]
+  pub locale: Option<String>,
+  #[serde(default)]
+  pub nonce: Option<String>,
+}
+
+/// The `aud` claim: a single client ID or, as OpenID Connect allows, several.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Audience {
+  One(String),
+  Many(Vec<String>),
+}
+
+impl Audience {
+  pub fn iter(&self) -> impl Iterator<Item = &str> {
+    let audiences = match self {
+      Self::One(audience) => std::slice::from_ref(audience),
+      Self::Many(audiences) => audiences.as_slice(),
+    };
+    audiences.iter().map(String::as_str)
+  }
+
+  pub fn contains(&self, client_id: &str) -> bool {
+    self.iter().any(|audience| audience == client_id)
+  }
+}
+
+impl Default for Audience {
+  fn default() -> Self {
+    Self::Many(Vec::new())
+  }
+}
+
+impl From<&str> for Audience {
+  fn from(audience: &str) -> Self {
+    Self::One(audience.to_string())
+  }
+}
+
+impl From<String> for Audience {
+  fn from(audience: String) -> Self {
+    Self::One(audience)
+  }
+}
+
+/// Public profile of the signed-in account, as carried by auth state events.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountSummary {
+  /// Stable Google account ID (`sub`).
+  pub id: String,
+  pub email: Option<String>,
+  pub display_name: Option<String>,
+  pub picture: Option<String>,
+}
+
+impl From<&IdTokenClaims> for AccountSummary {
+  fn from(claims: &IdTokenClaims) -> Self {
+    Self {
+      id: claims.sub.clone(),
+      email: claims.email.clone(),
+      display_name: claims.name.clone(),
+      picture: claims.picture.clone(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuthState {
+  SignedIn,
+  SignedOut,
+  TokenRefreshed,
+  /// The session can no longer be refreshed and was dropped.
+  Expired,
+  /// Another stored account became the active one.
+  AccountSwitched,
+  /// A stored account was forgotten.
+  AccountRemoved,
+}
+
+/// Payload of the `google-auth://state-changed` event.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthStateChange {
+  pub state: AuthState,
+  /// The affected account; absent once signed out.
+  pub account: Option<AccountSummary>,
+  /// The account requests are made for after the change, if any.
+  pub active_account: Option<AccountSummary>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListAccountsResponse {
+  pub accounts: Vec<AccountSummary>,
+  /// [`AccountSummary::id`] of the active account.
+  pub active_account_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountRequest {
+  /// [`AccountSummary::id`] of a stored account.
+  pub id: String,
+}
+
+/// Outcome of the silent session restore the plugin runs at startup.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoredSession {
+  pub signed_in: bool,
+  pub account: Option<AccountSummary>,
+  /// Why the tokens could not be refreshed although the session was kept, e.g.
+  /// `network` when starting offline.
+  pub refresh_error: Option<ErrorCode>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthReadiness {
+  /// Whether the startup restore has finished.
+  pub ready: bool,
+  pub session: Option<RestoredSession>,
+}
+
+/// Profile of the signed-in account, as returned by `get_current_user`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CurrentUser {
+  /// Stable Google account ID (`sub`).
+  pub id: String,
+  pub email: Option<String>,
+  pub email_verified: Option<bool>,
+  pub name: Option<String>,
+  pub given_name: Option<String>,
+  pub family_name: Option<String>,
+  pub hd: Option<String>,
+  pub locale: Option<String>,
+  /// Remote profile picture URL.
+  pub picture: Option<String>,
+  /// Resized local copy of `picture` in the app cache dir. Load it in the webview
+  /// with `convertFileSrc`.
+  pub avatar_path: Option<PathBuf>,
+}
+
+impl CurrentUser {
+  pub(crate) fn new(claims: IdTokenClaims, avatar_path: Option<PathBuf>) -> Self {
+    Self {
+      id: claims.sub,
+      email: claims.email,
+      email_verified: claims.email_verified,
+      name: claims.name,
+      given_name: claims.given_name,
+      family_name: claims.family_name,
+      hd: claims.hd,
+      locale: claims.locale,
+      picture: claims.picture,
+      avatar_path,
+    }
+  }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyIdTokenRequest {
+  pub id_token: String,
+  pub nonce: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestScopesRequest {
+  pub scopes: Vec<String>,
+}
+
+/// Outcome of an incremental authorization, split by requested scope.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestScopesResponse {
+  pub granted: Vec<String>,
+  pub denied: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceSignInRequest {
+  /// Identity provider, as in [`GoogleSignInRequest::provider`]. It has to support
+  /// the device flow.
+  pub provider: Option<String>,
+}
+
+/// What to show on the kiosk while the user approves the sign-in on their phone.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceSignInResponse {
+  pub user_code: String,
+  pub verification_uri: String,
+  /// Verification URI with the user code filled in, when the provider offers one.
+  pub verification_uri_complete: Option<String>,
+  /// Text to encode in a QR code: the complete verification URI when there is one.
+  pub qr_payload: String,
+  /// Unix time at which the user code expires.
+  pub expires_at: u64,
+  /// Seconds between polls of the token endpoint.
+  pub interval: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompleteDeviceSignInRequest {
+  pub user_code: String,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetTokensRequest {
+  pub force_refresh: Option<bool>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
-pub struct GoogleSignOutResponse {
-  pub success: bool,
-  pub error: Option<String>,
+pub struct GetTokensResponse {
+  pub id_token: String,
+  /// Absent on Android, where Credential Manager only issues ID tokens.
+  pub access_token: Option<String>,
+  /// Unix time at which the access token expires.
+  pub expires_at: Option<u64>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct IsSignedInResponse {
   pub is_signed_in: bool,
-  pub error: Option<String>,
+}
+
+fn bool_or_string<'de, D>(deserializer: D) -> std::result::Result<Option<bool>, D::Error>
+where
+  D: serde::Deserializer<'de>,
+{
+  #[derive(Deserialize)]
+  #[serde(untagged)]
+  enum BoolOrString {
+    Bool(bool),
+    String(String),
+  }
+
+  Ok(match Option::<BoolOrString>::deserialize(deserializer)? {
+    Some(BoolOrString::Bool(value)) => Some(value),
+    Some(BoolOrString::String(value)) => value.parse().ok(),
+    None => None,
+  })
 }
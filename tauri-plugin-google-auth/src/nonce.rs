@@ -0,0 +1,42 @@
+use std::{
+  collections::HashMap,
+  sync::Mutex,
+  time::{Duration, Instant},
+};
+
+use crate::{pkce::random_token, Error};
+
+/// How long a nonce stays blocked after use. ID tokens live for an hour, so a
+/// replayed token carrying an older nonce is rejected as expired anyway.
+const RETENTION: Duration = Duration::from_secs(3600 + 300);
+
+/// Nonces handed out to sign-in attempts, kept to refuse reuse.
+#[derive(Default)]
+pub(crate) struct NonceRegistry {
+  issued: Mutex<HashMap<String, Instant>>,
+}
+
+/// The nonce bound to a single sign-in attempt.
+pub(crate) struct AttemptNonce(String);
+
+impl NonceRegistry {
+  /// Reserves a nonce for a new attempt: `requested` when the caller needs a
+  /// specific one, a fresh random value otherwise.
+  pub fn begin(&self, requested: Option<String>) -> crate::Result<AttemptNonce> {
+    let mut issued = self.issued.lock().unwrap();
+    issued.retain(|_, at| at.elapsed() < RETENTION);
+
+    let nonce = requested.unwrap_or_else(|| random_token(32));
+    if issued.contains_key(&nonce) {
+      return Err(Error::NonceReused);
+    }
+    issued.insert(nonce.clone(), Instant::now());
+    Ok(AttemptNonce(nonce))
+  }
+}
+
+impl AttemptNonce {
+  pub fn as_str(&self) -> &str {
+    &self.0
+  }
+}
@@ -0,0 +1,241 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+use url::Url;
+
+use crate::Error;
+
+pub(crate) const GOOGLE_AUTHORIZATION_ENDPOINT: &str = "https://accounts.google.com/o/oauth2/v2/auth";
+pub(crate) const GOOGLE_TOKEN_ENDPOINT: &str = "https://oauth2.googleapis.com/token";
+pub(crate) const GOOGLE_REVOCATION_ENDPOINT: &str = "https://oauth2.googleapis.com/revoke";
+pub(crate) const GOOGLE_DEVICE_AUTHORIZATION_ENDPOINT: &str = "https://oauth2.googleapis.com/device/code";
+
+/// OAuth client settings used by the browser based sign-in flow.
+#[derive(Debug, Clone)]
+pub(crate) struct ClientConfig {
+  pub client_id: Option<String>,
+  /// Desktop OAuth clients are issued a secret that Google still expects on the token
+  /// endpoint even with PKCE. It is not confidential.
+  pub client_secret: Option<String>,
+  pub authorization_endpoint: String,
+  pub token_endpoint: String,
+  /// RFC 7009 token revocation endpoint, when the provider has one.
+  pub revocation_endpoint: Option<String>,
+  /// RFC 8628 device authorization endpoint, when the provider has one.
+  pub device_authorization_endpoint: Option<String>,
+  pub scopes: Vec<String>,
+  /// Restricts the account picker to a Google Workspace domain (`hd`).
+  pub hosted_domain: Option<String>,
+}
+
+impl Default for ClientConfig {
+  fn default() -> Self {
+    Self {
+      client_id: None,
+      client_secret: None,
+      authorization_endpoint: GOOGLE_AUTHORIZATION_ENDPOINT.into(),
+      token_endpoint: GOOGLE_TOKEN_ENDPOINT.into(),
+      revocation_endpoint: Some(GOOGLE_REVOCATION_ENDPOINT.into()),
+      device_authorization_endpoint: Some(GOOGLE_DEVICE_AUTHORIZATION_ENDPOINT.into()),
+      scopes: Vec::new(),
+      hosted_domain: None,
+    }
+  }
+}
+
+impl ClientConfig {
+  pub fn client_id(&self) -> crate::Result<&str> {
+    self
+      .client_id
+      .as_deref()
+      .ok_or(Error::MissingConfig("client_id"))
+  }
+}
+
+/// Per-attempt parameters of an authorization request, generated by the flow.
+pub(crate) struct AuthorizationRequest<'a> {
+  pub redirect_uri: &'a str,
+  pub state: &'a str,
+  pub code_challenge: &'a str,
+}
+
+/// Caller-controlled options of an authorization request.
+#[derive(Debug, Default)]
+pub(crate) struct AuthorizationParams<'a> {
+  pub nonce: Option<&'a str>,
+  /// Forces the consent screen, which is the only way to get a new refresh token
+  /// for an account that already granted access.
+  pub force_consent: bool,
+  /// Preselects the account in the picker.
+  pub login_hint: Option<&'a str>,
+  /// Keeps previously granted scopes on the new tokens (incremental authorization).
+  pub include_granted_scopes: bool,
+}
+
+/// Successful response of the token endpoint.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct TokenResponse {
+  pub access_token: String,
+  #[serde(default)]
+  pub id_token: Option<String>,
+  #[serde(default)]
+  pub refresh_token: Option<String>,
+  #[serde(default)]
+  pub expires_in: Option<u64>,
+  #[serde(default)]
+  pub scope: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenErrorResponse {
+  error: String,
+  #[serde(default)]
+  error_description: Option<String>,
+}
+
+pub(crate) fn authorization_url(
+  config: &ClientConfig,
+  request: &AuthorizationRequest<'_>,
+  params: &AuthorizationParams<'_>,
+) -> crate::Result<Url> {
+  let mut url = Url::parse(&config.authorization_endpoint)?;
+  {
+    let mut query = url.query_pairs_mut();
+    query
+      .append_pair("client_id", config.client_id()?)
+      .append_pair("redirect_uri", request.redirect_uri)
+      .append_pair("response_type", "code")
+      .append_pair("scope", &config.scopes.join(" "))
+      .append_pair("state", request.state)
+      .append_pair("code_challenge", request.code_challenge)
+      .append_pair("code_challenge_method", "S256")
+      // Ask for a refresh token so the session outlives the one hour access token.
+      .append_pair("access_type", "offline");
+    if let Some(nonce) = params.nonce {
+      query.append_pair("nonce", nonce);
+    }
+    if let Some(hd) = config.hosted_domain.as_deref() {
+      query.append_pair("hd", hd);
+    }
+    if params.force_consent {
+      query.append_pair("prompt", "consent");
+    }
+    if let Some(login_hint) = params.login_hint {
+      query.append_pair("login_hint", login_hint);
+    }
+    if params.include_granted_scopes {
+      query.append_pair("include_granted_scopes", "true");
+    }
+  }
+  Ok(url)
+}
+
+/// Exchanges an authorization code for tokens.
+pub(crate) async fn exchange_code(
+  http: &reqwest::Client,
+  config: &ClientConfig,
+  code: &str,
+  code_verifier: &str,
+  redirect_uri: &str,
+) -> crate::Result<TokenResponse> {
+  let mut form = vec![
+    ("grant_type", "authorization_code"),
+    ("code", code),
+    ("code_verifier", code_verifier),
+    ("redirect_uri", redirect_uri),
+    ("client_id", config.client_id()?),
+  ];
+  if let Some(secret) = config.client_secret.as_deref() {
+    form.push(("client_secret", secret));
+  }
+  token_request(http, &config.token_endpoint, &form).await
+}
+
+/// Obtains fresh tokens with a refresh token.
+pub(crate) async fn refresh(
+  http: &reqwest::Client,
+  config: &ClientConfig,
+  refresh_token: &str,
+) -> crate::Result<TokenResponse> {
+  let mut form = vec![
+    ("grant_type", "refresh_token"),
+    ("refresh_token", refresh_token),
+    ("client_id", config.client_id()?),
+  ];
+  if let Some(secret) = config.client_secret.as_deref() {
+    form.push(("client_secret", secret));
+  }
+  token_request(http, &config.token_endpoint, &form).await
+}
+
+/// Revokes `token` and with it the whole grant. A token the server no longer
+/// knows counts as revoked.
+pub(crate) async fn revoke(http: &reqwest::Client, config: &ClientConfig, token: &str) -> crate::Result<()> {
+  let endpoint = config
+    .revocation_endpoint
+    .as_deref()
+    .ok_or(Error::MissingConfig("revocation_endpoint"))?;
+  let response = http
+    .post(endpoint)
+    .form(&[("token", token)])
+    .send()
+    .await?;
+  if response.status().is_success() {
+    return Ok(());
+  }
+
+  match error_response(response, "revocation endpoint").await {
+    Error::OAuth { error, .. } if error == "invalid_token" => Ok(()),
+    e => Err(e),
+  }
+}
+
+pub(crate) async fn token_request(
+  http: &reqwest::Client,
+  endpoint: &str,
+  form: &[(&str, &str)],
+) -> crate::Result<TokenResponse> {
+  let response = http.post(endpoint).form(form).send().await?;
+  if response.status().is_success() {
+    return Ok(response.json().await?);
+  }
+  Err(error_response(response, "token endpoint").await)
+}
+
+/// Turns an unsuccessful response of `endpoint` into an [`Error::OAuth`].
+pub(crate) async fn error_response(response: reqwest::Response, endpoint: &str) -> Error {
+  let status = response.status();
+  match response.json::<TokenErrorResponse>().await {
+    Ok(body) => Error::OAuth {
+      error: body.error,
+      description: body.error_description,
+    },
+    Err(_) => Error::OAuth {
+      error: "http_error".into(),
+      description: Some(format!("{endpoint} returned {status}")),
+    },
+  }
+}
+
+/// Splits a space separated OAuth `scope` value.
+pub(crate) fn split_scopes(scope: Option<&str>) -> Option<Vec<String>> {
+  scope.map(|scope| scope.split_whitespace().map(String::from).collect())
+}
+
+/// Whether `granted` covers the requested `scope`, accounting for Google reporting
+/// the `email` and `profile` shorthands by their full URLs.
+pub(crate) fn scope_granted(granted: &[String], scope: &str) -> bool {
+  let full = match scope {
+    "email" => "https://www.googleapis.com/auth/userinfo.email",
+    "profile" => "https://www.googleapis.com/auth/userinfo.profile",
+    other => other,
+  };
+  granted.iter().any(|g| g == scope || g == full)
+}
+
+pub(crate) fn unix_now() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or_default()
+}
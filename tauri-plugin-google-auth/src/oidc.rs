@@ -0,0 +1,242 @@
+use std::{collections::HashMap, sync::Arc};
+
+use serde::Deserialize;
+use tokio::sync::Mutex;
+
+use crate::{
+  config::ProviderConfig,
+  id_token::{IdTokenVerifier, VerifierConfig},
+  oauth::ClientConfig,
+  Error,
+};
+
+/// Name of the built-in provider configured by the top-level plugin settings.
+pub(crate) const GOOGLE: &str = "google";
+
+const DEFAULT_SCOPES: &[&str] = &["openid", "email", "profile"];
+
+/// The parts of an OpenID Provider Metadata document (OpenID Connect Discovery
+/// 1.0, section 3) the plugin uses.
+#[derive(Debug, Deserialize)]
+struct ProviderMetadata {
+  issuer: String,
+  authorization_endpoint: String,
+  token_endpoint: String,
+  jwks_uri: String,
+  #[serde(default)]
+  revocation_endpoint: Option<String>,
+  #[serde(default)]
+  device_authorization_endpoint: Option<String>,
+  #[serde(default)]
+  scopes_supported: Vec<String>,
+}
+
+/// Everything needed to sign in with one identity provider.
+pub(crate) struct Provider {
+  pub client: ClientConfig,
+  pub verifier: IdTokenVerifier,
+}
+
+/// Identity providers available to the desktop flow, resolved on first use.
+pub(crate) struct Providers {
+  http: reqwest::Client,
+  configs: HashMap<String, ProviderConfig>,
+  resolved: Mutex<HashMap<String, Arc<Provider>>>,
+}
+
+impl Providers {
+  /// `google` is the preset built from the top-level configuration; `configs`
+  /// declares the others.
+  pub fn new(
+    http: reqwest::Client,
+    google: ClientConfig,
+    google_verifier: VerifierConfig,
+    configs: HashMap<String, ProviderConfig>,
+  ) -> Self {
+    let google = Provider {
+      verifier: IdTokenVerifier::new(http.clone(), google_verifier),
+      client: google,
+    };
+    Self {
+      http,
+      configs,
+      resolved: Mutex::new(HashMap::from([(GOOGLE.to_string(), Arc::new(google))])),
+    }
+  }
+
+  /// Adds a provider whose endpoints are known up front.
+  pub fn preset(mut self, name: &str, provider: Provider) -> Self {
+    self.resolved.get_mut().insert(name.to_string(), Arc::new(provider));
+    self
+  }
+
+  pub async fn get(&self, name: &str) -> crate::Result<Arc<Provider>> {
+    let mut resolved = self.resolved.lock().await;
+    if let Some(provider) = resolved.get(name) {
+      return Ok(provider.clone());
+    }
+
+    let config = self
+      .configs
+      .get(name)
+      .ok_or_else(|| Error::InvalidConfig(format!("unknown provider `{name}`")))?;
+    let metadata = discover(&self.http, &config.issuer).await?;
+    let provider = Arc::new(Provider {
+      client: ClientConfig {
+        client_id: Some(config.client_id.clone()),
+        client_secret: config.client_secret.clone(),
+        authorization_endpoint: metadata.authorization_endpoint,
+        token_endpoint: metadata.token_endpoint,
+        revocation_endpoint: metadata.revocation_endpoint,
+        device_authorization_endpoint: metadata.device_authorization_endpoint,
+        scopes: scopes(config, &metadata.scopes_supported),
+        hosted_domain: None,
+      },
+      verifier: IdTokenVerifier::new(
+        self.http.clone(),
+        VerifierConfig {
+          jwks_uri: metadata.jwks_uri,
+          issuers: vec![metadata.issuer],
+          audiences: vec![config.client_id.clone()],
+        },
+      ),
+    });
+    resolved.insert(name.to_string(), provider.clone());
+    Ok(provider)
+  }
+}
+
+/// Fetches and validates the discovery document of `issuer`.
+async fn discover(http: &reqwest::Client, issuer: &str) -> crate::Result<ProviderMetadata> {
+  let issuer = issuer.trim_end_matches('/');
+  let metadata: ProviderMetadata = http
+    .get(format!("{issuer}/.well-known/openid-configuration"))
+    .send()
+    .await?
+    .error_for_status()?
+    .json()
+    .await?;
+
+  // The document must describe the issuer it was fetched from (section 4.3).
+  if metadata.issuer.trim_end_matches('/') != issuer {
+    return Err(Error::InvalidConfig(format!(
+      "discovery document of `{issuer}` is for issuer `{}`",
+      metadata.issuer
+    )));
+  }
+  Ok(metadata)
+}
+
+/// Configured scopes, or the defaults the provider says it supports.
+fn scopes(config: &ProviderConfig, supported: &[String]) -> Vec<String> {
+  if !config.scopes.is_empty() {
+    return config.scopes.clone();
+  }
+  DEFAULT_SCOPES
+    .iter()
+    .filter(|scope| **scope == "openid" || supported.is_empty() || supported.iter().any(|s| s == *scope))
+    .map(|scope| scope.to_string())
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Mutex as StdMutex;
+
+  use serde_json::{json, Value};
+
+  use super::*;
+  use crate::test_support::{claims, jwks, keys, Response, StandIn};
+
+  const REALM: &str = "/realms/be-out";
+  const CLIENT_ID: &str = "be-out-desktop";
+
+  /// A Keycloak-style realm whose discovery document claims `issuer`, or the
+  /// realm's own URL when `None`.
+  async fn keycloak(issuer: Option<&'static str>) -> (Providers, StandIn) {
+    let url = Arc::new(StdMutex::new(String::new()));
+    let base = url.clone();
+    let idp = StandIn::start(move |request| {
+      let realm = format!("{}{REALM}", base.lock().unwrap());
+      match request.path.strip_prefix(REALM) {
+        Some("/.well-known/openid-configuration") => Response::json(metadata(&realm, issuer)),
+        Some("/protocol/openid-connect/certs") => Response::json(jwks(&[&keys()[0]])),
+        _ => Response::not_found(),
+      }
+    })
+    .await;
+    *url.lock().unwrap() = idp.url.clone();
+
+    let providers = Providers::new(
+      reqwest::Client::new(),
+      ClientConfig::default(),
+      VerifierConfig::default(),
+      HashMap::from([(
+        "keycloak".to_string(),
+        ProviderConfig {
+          issuer: format!("{}{REALM}/", idp.url),
+          client_id: CLIENT_ID.into(),
+          client_secret: None,
+          scopes: Vec::new(),
+        },
+      )]),
+    );
+    (providers, idp)
+  }
+
+  fn metadata(realm: &str, issuer: Option<&str>) -> Value {
+    let endpoint = |path: &str| format!("{realm}/protocol/openid-connect/{path}");
+    json!({
+      "issuer": issuer.map_or_else(|| realm.to_string(), str::to_string),
+      "authorization_endpoint": endpoint("auth"),
+      "token_endpoint": endpoint("token"),
+      "jwks_uri": endpoint("certs"),
+      "revocation_endpoint": endpoint("revoke"),
+      "device_authorization_endpoint": endpoint("auth/device"),
+      "scopes_supported": ["openid", "email", "offline_access"],
+    })
+  }
+
+  #[tokio::test]
+  async fn configures_a_provider_from_its_discovery_document() {
+    let (providers, idp) = keycloak(None).await;
+    let realm = format!("{}{REALM}", idp.url);
+
+    let provider = providers.get("keycloak").await.unwrap();
+    let client = &provider.client;
+    assert_eq!(client.client_id.as_deref(), Some(CLIENT_ID));
+    assert_eq!(client.authorization_endpoint, format!("{realm}/protocol/openid-connect/auth"));
+    assert_eq!(client.token_endpoint, format!("{realm}/protocol/openid-connect/token"));
+    assert_eq!(
+      client.revocation_endpoint.as_deref(),
+      Some(format!("{realm}/protocol/openid-connect/revoke").as_str())
+    );
+    // `profile` is not supported by this realm.
+    assert_eq!(client.scopes, ["openid", "email"]);
+
+    // ID tokens are checked against the discovered keys and issuer.
+    let token = keys()[0].sign(&claims(&realm, CLIENT_ID));
+    assert_eq!(provider.verifier.verify(&token, None).await.unwrap().iss, realm);
+    let token = keys()[0].sign(&claims("https://accounts.google.com", CLIENT_ID));
+    assert!(provider.verifier.verify(&token, None).await.is_err());
+  }
+
+  #[tokio::test]
+  async fn rejects_a_document_for_another_issuer() {
+    let (providers, _idp) = keycloak(Some("https://evil.example/realms/be-out")).await;
+
+    assert!(matches!(providers.get("keycloak").await, Err(Error::InvalidConfig(_))));
+  }
+
+  #[tokio::test]
+  async fn discovers_each_provider_once() {
+    let (providers, idp) = keycloak(None).await;
+    let discovery = format!("{REALM}/.well-known/openid-configuration");
+
+    let first = providers.get("keycloak").await.unwrap();
+    let second = providers.get("keycloak").await.unwrap();
+    assert!(Arc::ptr_eq(&first, &second));
+    assert_eq!(idp.hits(&discovery), 1);
+    assert!(matches!(providers.get("okta").await, Err(Error::InvalidConfig(_))));
+  }
+}
@@ -0,0 +1,34 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+/// Returns `len` random bytes encoded as unpadded base64url.
+pub fn random_token(len: usize) -> String {
+  let mut bytes = vec![0u8; len];
+  rand::thread_rng().fill_bytes(&mut bytes);
+  URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// A PKCE (RFC 7636) verifier and its S256 challenge.
+pub struct Pkce {
+  pub verifier: String,
+  pub challenge: String,
+}
+
+impl Pkce {
+  pub fn new() -> Self {
+    // 32 random bytes encode to a 43 character verifier, the minimum allowed length.
+    let verifier = random_token(32);
+    let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+    Self {
+      verifier,
+      challenge,
+    }
+  }
+}
+
+impl Default for Pkce {
+  fn default() -> Self {
+    Self::new()
+  }
+}
@@ -0,0 +1,64 @@
+use crate::{config::AccountPolicy, models::IdTokenClaims, Error};
+
+impl AccountPolicy {
+  /// Checks verified claims against the policy. The error names the first rule
+  /// that failed.
+  pub(crate) fn check(&self, claims: &IdTokenClaims) -> crate::Result<()> {
+    if !self.allowed_hosted_domains.is_empty() {
+      let allowed = claims.hd.as_deref().is_some_and(|hd| {
+        self
+          .allowed_hosted_domains
+          .iter()
+          .any(|domain| domain.eq_ignore_ascii_case(hd))
+      });
+      if !allowed {
+        return Err(Error::PolicyViolation("hosted_domain"));
+      }
+    }
+
+    if self.require_verified_email && claims.email_verified != Some(true) {
+      return Err(Error::PolicyViolation("email_verified"));
+    }
+
+    if self.allowed_emails.is_empty() && self.denied_emails.is_empty() {
+      return Ok(());
+    }
+    let email = claims
+      .email
+      .as_deref()
+      .ok_or(Error::PolicyViolation("email_missing"))?
+      .to_ascii_lowercase();
+    if self.denied_emails.iter().any(|pattern| matches(pattern, &email)) {
+      return Err(Error::PolicyViolation("email_denied"));
+    }
+    if !self.allowed_emails.is_empty() && !self.allowed_emails.iter().any(|pattern| matches(pattern, &email)) {
+      return Err(Error::PolicyViolation("email_not_allowed"));
+    }
+    Ok(())
+  }
+}
+
+/// Matches a lowercase `email` against `pattern`, where `*` stands for any run
+/// of characters.
+fn matches(pattern: &str, email: &str) -> bool {
+  let pattern = pattern.trim().to_ascii_lowercase();
+  let mut parts = pattern.split('*');
+  // `split` always yields at least one part.
+  let first = parts.next().unwrap_or_default();
+  let Some(mut rest) = email.strip_prefix(first) else {
+    return false;
+  };
+
+  let mut parts: Vec<&str> = parts.collect();
+  let Some(last) = parts.pop() else {
+    // No wildcard: the whole email had to match.
+    return rest.is_empty();
+  };
+  for part in parts {
+    match rest.find(part) {
+      Some(index) => rest = &rest[index + part.len()..],
+      None => return false,
+    }
+  }
+  rest.ends_with(last)
+}
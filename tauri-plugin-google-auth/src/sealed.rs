@@ -0,0 +1,162 @@
+use std::{
+  fs,
+  io::{self, Write},
+  path::{Path, PathBuf},
+};
+
+use aes_gcm::{
+  aead::{Aead, AeadCore, KeyInit, OsRng},
+  Aes256Gcm, Key, Nonce,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+const KEY_FILE: &str = "session.key";
+const NONCE_LEN: usize = 12;
+
+/// A directory of JSON files sealed with AES-256-GCM.
+///
+/// The key lives in a separate, owner-only file in the same directory, so this
+/// protects against casual reads of the data dir (backups, sync clients), not
+/// against code running as the same user. On Android and iOS the data dir is
+/// private to the app anyway.
+pub struct SealedDir {
+  dir: PathBuf,
+  cipher: Aes256Gcm,
+}
+
+impl SealedDir {
+  /// Opens `dir`, creating it and its key on first use. A malformed key is
+  /// replaced, which makes every file sealed with it unreadable.
+  pub fn open(dir: &Path) -> io::Result<Self> {
+    fs::create_dir_all(dir)?;
+    Ok(Self {
+      cipher: Aes256Gcm::new(&load_or_create_key(&dir.join(KEY_FILE))?),
+      dir: dir.to_path_buf(),
+    })
+  }
+
+  /// Reads and decrypts `file`. Returns `Ok(None)` when it does not exist and an
+  /// [`io::ErrorKind::InvalidData`] error when it cannot be decrypted or parsed.
+  pub fn read<T: DeserializeOwned>(&self, file: &str) -> io::Result<Option<T>> {
+    let bytes = match fs::read(self.dir.join(file)) {
+      Ok(bytes) => bytes,
+      Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+      Err(e) => return Err(e),
+    };
+    decrypt(&self.cipher, &bytes)
+      .map(Some)
+      .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("{file} cannot be unsealed")))
+  }
+
+  /// Atomically replaces `file` with `value`, sealed.
+  pub fn write<T: Serialize>(&self, file: &str, value: &T) -> io::Result<()> {
+    write_private(&self.dir.join(file), &encrypt(&self.cipher, value)?)
+  }
+
+  /// Deletes `file`; a file that does not exist is not an error.
+  pub fn remove(&self, file: &str) -> io::Result<()> {
+    match fs::remove_file(self.dir.join(file)) {
+      Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+      _ => Ok(()),
+    }
+  }
+}
+
+fn encrypt<T: Serialize>(cipher: &Aes256Gcm, value: &T) -> io::Result<Vec<u8>> {
+  let plaintext = serde_json::to_vec(value)?;
+  let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+  let ciphertext = cipher
+    .encrypt(&nonce, plaintext.as_slice())
+    .map_err(|_| io::Error::other("failed to encrypt sealed file"))?;
+
+  let mut contents = nonce.to_vec();
+  contents.extend_from_slice(&ciphertext);
+  Ok(contents)
+}
+
+fn decrypt<T: DeserializeOwned>(cipher: &Aes256Gcm, bytes: &[u8]) -> Option<T> {
+  if bytes.len() < NONCE_LEN {
+    return None;
+  }
+  let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+  let plaintext = cipher.decrypt(Nonce::from_slice(nonce), ciphertext).ok()?;
+  serde_json::from_slice(&plaintext).ok()
+}
+
+fn load_or_create_key(path: &Path) -> io::Result<Key<Aes256Gcm>> {
+  match fs::read(path) {
+    Ok(bytes) if bytes.len() == 32 => return Ok(*Key::<Aes256Gcm>::from_slice(&bytes)),
+    Ok(_) => log::warn!("Replacing malformed key {}", path.display()),
+    Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+    Err(e) => return Err(e),
+  }
+
+  let key = Aes256Gcm::generate_key(&mut OsRng);
+  write_private(path, &key)?;
+  Ok(key)
+}
+
+/// Atomically replaces `path` with `contents`, readable by the owner only.
+fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+  let tmp = path.with_extension("tmp");
+  let mut options = fs::OpenOptions::new();
+  options.write(true).create(true).truncate(true);
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::OpenOptionsExt;
+    options.mode(0o600);
+  }
+
+  let mut file = options.open(&tmp)?;
+  file.write_all(contents)?;
+  file.sync_all()?;
+  fs::rename(tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::{json, Value};
+
+  use super::*;
+  use crate::test_support::temp_dir;
+
+  #[test]
+  fn round_trips_through_a_sealed_file() {
+    let dir = temp_dir();
+    let sealed = SealedDir::open(&dir).unwrap();
+    let value = json!({ "refresh_token": "1//secret" });
+
+    assert_eq!(sealed.read::<Value>("tokens.bin").unwrap(), None);
+    sealed.write("tokens.bin", &value).unwrap();
+    let on_disk = fs::read(dir.join("tokens.bin")).unwrap();
+    assert!(!String::from_utf8_lossy(&on_disk).contains("1//secret"));
+
+    // A second handle reads it with the key stored next to it.
+    let reopened = SealedDir::open(&dir).unwrap();
+    assert_eq!(reopened.read::<Value>("tokens.bin").unwrap(), Some(value));
+    reopened.remove("tokens.bin").unwrap();
+    reopened.remove("tokens.bin").unwrap();
+    assert_eq!(sealed.read::<Value>("tokens.bin").unwrap(), None);
+  }
+
+  #[test]
+  fn refuses_files_it_cannot_unseal() {
+    let dir = temp_dir();
+    let sealed = SealedDir::open(&dir).unwrap();
+    sealed.write("tokens.bin", &json!({ "refresh_token": "1//secret" })).unwrap();
+
+    let mut tampered = fs::read(dir.join("tokens.bin")).unwrap();
+    *tampered.last_mut().unwrap() ^= 1;
+    fs::write(dir.join("tampered.bin"), tampered).unwrap();
+    fs::write(dir.join("short.bin"), b"short").unwrap();
+    for file in ["tampered.bin", "short.bin"] {
+      let error = sealed.read::<Value>(file).unwrap_err();
+      assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    // A malformed key is replaced, which leaves older files unreadable.
+    fs::write(dir.join(KEY_FILE), b"not a key").unwrap();
+    let rekeyed = SealedDir::open(&dir).unwrap();
+    assert_eq!(rekeyed.read::<Value>("tokens.bin").unwrap_err().kind(), io::ErrorKind::InvalidData);
+  }
+}
@@ -0,0 +1,288 @@
+use std::{io, path::Path, sync::Mutex};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{models::IdTokenClaims, oauth::unix_now, sealed::SealedDir};
+
+const SESSION_FILE: &str = "session.bin";
+
+/// Account and tokens persisted between launches.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) struct StoredSession {
+  /// Name of the identity provider the session belongs to.
+  #[serde(default = "default_provider")]
+  pub provider: String,
+  /// Signed in with the device flow, whose tokens belong to a different client.
+  #[serde(default)]
+  pub device: bool,
+  pub claims: IdTokenClaims,
+  pub id_token: String,
+  #[serde(default)]
+  pub access_token: Option<String>,
+  #[serde(default)]
+  pub refresh_token: Option<String>,
+  /// Unix time at which the access token expires.
+  #[serde(default)]
+  pub expires_at: Option<u64>,
+  #[serde(default)]
+  pub scopes: Vec<String>,
+}
+
+fn default_provider() -> String {
+  crate::oidc::GOOGLE.into()
+}
+
+impl StoredSession {
+  /// Whether the access token expires within `margin` seconds.
+  pub fn expires_within(&self, margin: u64) -> bool {
+    match (&self.access_token, self.expires_at) {
+      (Some(_), Some(expires_at)) => expires_at <= unix_now() + margin,
+      (Some(_), None) => false,
+      (None, _) => true,
+    }
+  }
+}
+
+/// Every signed-in account and which one is active, as persisted.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+struct StoredAccounts {
+  /// `sub` of the active account.
+  #[serde(default)]
+  active: Option<String>,
+  sessions: Vec<StoredSession>,
+}
+
+impl StoredAccounts {
+  fn position(&self, id: &str) -> Option<usize> {
+    self.sessions.iter().position(|session| session.claims.sub == id)
+  }
+
+  fn active(&self) -> Option<&StoredSession> {
+    let id = self.active.as_deref()?;
+    self.sessions.iter().find(|session| session.claims.sub == id)
+  }
+}
+
+/// Accounts persisted in a [`SealedDir`] in the app data dir, each with its own
+/// tokens. One of them may be active; it is the one the plugin signs requests
+/// for.
+pub(crate) struct SessionStore {
+  sealed: SealedDir,
+  accounts: Mutex<StoredAccounts>,
+}
+
+impl SessionStore {
+  /// Opens the store in `dir`, creating the key on first use.
+  ///
+  /// Sessions that cannot be decrypted (corrupted file, lost key) are discarded
+  /// rather than failing plugin setup. A single-account session written by an
+  /// earlier version becomes the active account.
+  pub fn open(dir: &Path) -> crate::Result<Self> {
+    let sealed = SealedDir::open(dir)?;
+    let accounts = match sealed.read::<Value>(SESSION_FILE) {
+      Ok(None) => Some(StoredAccounts::default()),
+      Ok(Some(stored)) => serde_json::from_value::<StoredAccounts>(stored.clone()).ok().or_else(|| {
+        serde_json::from_value::<StoredSession>(stored).ok().map(|session| StoredAccounts {
+          active: Some(session.claims.sub.clone()),
+          sessions: vec![session],
+        })
+      }),
+      Err(e) if e.kind() == io::ErrorKind::InvalidData => None,
+      Err(e) => return Err(e.into()),
+    };
+    let accounts = accounts.unwrap_or_else(|| {
+      log::warn!("Discarding unreadable google-auth sessions");
+      let _ = sealed.remove(SESSION_FILE);
+      StoredAccounts::default()
+    });
+
+    Ok(Self {
+      sealed,
+      accounts: Mutex::new(accounts),
+    })
+  }
+
+  /// The session of the active account.
+  pub fn get(&self) -> Option<StoredSession> {
+    self.accounts.lock().unwrap().active().cloned()
+  }
+
+  /// The session of the account with `sub` `id`, active or not.
+  pub fn account(&self, id: &str) -> Option<StoredSession> {
+    let accounts = self.accounts.lock().unwrap();
+    accounts.position(id).map(|index| accounts.sessions[index].clone())
+  }
+
+  pub fn list(&self) -> Vec<StoredSession> {
+    self.accounts.lock().unwrap().sessions.clone()
+  }
+
+  /// Stores the session of a fresh sign-in and makes its account the active one.
+  pub fn sign_in(&self, session: StoredSession) -> crate::Result<()> {
+    self.update(|accounts| {
+      accounts.active = Some(session.claims.sub.clone());
+      match accounts.position(&session.claims.sub) {
+        Some(index) => accounts.sessions[index] = session,
+        None => accounts.sessions.push(session),
+      }
+      true
+    })?;
+    Ok(())
+  }
+
+  /// Updates the session of a stored account without changing which account is
+  /// active. Does nothing when the account was removed in the meantime.
+  pub fn save(&self, session: StoredSession) -> crate::Result<()> {
+    self.update(|accounts| match accounts.position(&session.claims.sub) {
+      Some(index) => {
+        accounts.sessions[index] = session;
+        true
+      }
+      None => false,
+    })?;
+    Ok(())
+  }
+
+  /// Makes `id` the active account. Returns `false` when there is no such account.
+  pub fn activate(&self, id: &str) -> crate::Result<bool> {
+    self.update(|accounts| {
+      let known = accounts.position(id).is_some();
+      if known {
+        accounts.active = Some(id.to_string());
+      }
+      known
+    })
+  }
+
+  /// Forgets the account `id` and its tokens. Returns `false` when there is no
+  /// such account.
+  pub fn remove(&self, id: &str) -> crate::Result<bool> {
+    self.update(|accounts| {
+      let Some(index) = accounts.position(id) else {
+        return false;
+      };
+      accounts.sessions.remove(index);
+      if accounts.active.as_deref() == Some(id) {
+        accounts.active = None;
+      }
+      true
+    })
+  }
+
+  /// Forgets the active account and its tokens; the others stay signed in.
+  pub fn clear(&self) -> crate::Result<()> {
+    let active = self.accounts.lock().unwrap().active.clone();
+    if let Some(id) = active {
+      self.remove(&id)?;
+    }
+    Ok(())
+  }
+
+  /// Applies `change` and persists the result when it reports a modification.
+  /// The in-memory state is only replaced once the file was written.
+  fn update(&self, change: impl FnOnce(&mut StoredAccounts) -> bool) -> crate::Result<bool> {
+    let mut accounts = self.accounts.lock().unwrap();
+    let mut updated = accounts.clone();
+    if !change(&mut updated) {
+      return Ok(false);
+    }
+
+    if updated.sessions.is_empty() {
+      self.sealed.remove(SESSION_FILE)?;
+    } else {
+      self.sealed.write(SESSION_FILE, &updated)?;
+    }
+    *accounts = updated;
+    Ok(true)
+  }
+
+  /// Reads `file` next to the sessions, sealed with the same key. For data that
+  /// has to outlive sign-out.
+  pub fn read_sealed<T: DeserializeOwned>(&self, file: &str) -> Option<T> {
+    self.sealed.read(file).ok().flatten()
+  }
+
+  pub fn write_sealed<T: Serialize>(&self, file: &str, value: &T) -> crate::Result<()> {
+    self.sealed.write(file, value)?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_support::temp_dir;
+
+  fn session(sub: &str) -> StoredSession {
+    StoredSession {
+      provider: crate::oidc::GOOGLE.into(),
+      device: false,
+      claims: IdTokenClaims {
+        sub: sub.into(),
+        email: Some(format!("{sub}@be-out.app")),
+        ..Default::default()
+      },
+      id_token: format!("id-token-{sub}"),
+      access_token: Some(format!("access-token-{sub}")),
+      refresh_token: Some(format!("refresh-token-{sub}")),
+      expires_at: Some(unix_now() + 3600),
+      scopes: vec!["openid".into()],
+    }
+  }
+
+  #[test]
+  fn discards_sessions_it_cannot_decrypt() {
+    let dir = temp_dir();
+    SessionStore::open(&dir).unwrap().sign_in(session("ada")).unwrap();
+    assert_eq!(SessionStore::open(&dir).unwrap().get().unwrap().claims.sub, "ada");
+
+    std::fs::write(dir.join(SESSION_FILE), b"garbage that is not sealed").unwrap();
+    let store = SessionStore::open(&dir).unwrap();
+    assert!(store.get().is_none());
+    assert!(store.list().is_empty());
+    assert!(!dir.join(SESSION_FILE).exists());
+  }
+
+  #[test]
+  fn switches_between_accounts() {
+    let dir = temp_dir();
+    let store = SessionStore::open(&dir).unwrap();
+    store.sign_in(session("ada")).unwrap();
+    store.sign_in(session("grace")).unwrap();
+    assert_eq!(store.get().unwrap().claims.sub, "grace");
+    assert_eq!(store.list().len(), 2);
+
+    assert!(store.activate("ada").unwrap());
+    assert_eq!(store.get().unwrap().claims.sub, "ada");
+    assert!(!store.activate("linus").unwrap());
+    assert_eq!(store.get().unwrap().claims.sub, "ada");
+
+    // The choice survives a restart.
+    let reopened = SessionStore::open(&dir).unwrap();
+    assert_eq!(reopened.get().unwrap().claims.sub, "ada");
+    assert_eq!(reopened.list().len(), 2);
+  }
+
+  #[test]
+  fn removing_the_active_account_keeps_the_others() {
+    let dir = temp_dir();
+    let store = SessionStore::open(&dir).unwrap();
+    store.sign_in(session("ada")).unwrap();
+    store.sign_in(session("grace")).unwrap();
+
+    assert!(store.remove("grace").unwrap());
+    assert!(store.get().is_none());
+    assert_eq!(store.list().len(), 1);
+    assert_eq!(store.account("ada").unwrap().refresh_token.as_deref(), Some("refresh-token-ada"));
+    assert!(!store.remove("grace").unwrap());
+
+    let reopened = SessionStore::open(&dir).unwrap();
+    assert!(reopened.get().is_none());
+    assert_eq!(reopened.list().len(), 1);
+
+    // Removing the last account leaves nothing on disk.
+    assert!(reopened.remove("ada").unwrap());
+    assert!(!dir.join(SESSION_FILE).exists());
+  }
+}
@@ -0,0 +1,235 @@
+//! Local stand-ins for identity providers, for the unit tests.
+
+use std::{
+  collections::HashMap,
+  path::PathBuf,
+  sync::{Arc, Mutex, OnceLock},
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use rsa::{pkcs1::EncodeRsaPrivateKey, traits::PublicKeyParts, RsaPrivateKey};
+use serde_json::{json, Value};
+use tokio::{
+  io::{AsyncReadExt, AsyncWriteExt},
+  net::{TcpListener, TcpStream},
+};
+
+use crate::{oauth::unix_now, pkce::random_token};
+
+/// A request received by a [`StandIn`].
+#[derive(Debug, Clone)]
+pub(crate) struct Request {
+  pub path: String,
+  pub query: HashMap<String, String>,
+  /// Form encoded body, empty for a `GET`.
+  pub form: HashMap<String, String>,
+}
+
+pub(crate) struct Response {
+  status: u16,
+  headers: Vec<(String, String)>,
+  body: String,
+}
+
+impl Response {
+  pub fn json(body: Value) -> Self {
+    Self {
+      status: 200,
+      headers: vec![("Content-Type".into(), "application/json".into())],
+      body: body.to_string(),
+    }
+  }
+
+  /// A `302` to `location`.
+  pub fn redirect(location: &str) -> Self {
+    Self::json(Value::Null).status(302).header("Location", location)
+  }
+
+  pub fn not_found() -> Self {
+    Self::json(json!({ "error": "not_found" })).status(404)
+  }
+
+  pub fn status(mut self, status: u16) -> Self {
+    self.status = status;
+    self
+  }
+
+  pub fn header(mut self, name: &str, value: &str) -> Self {
+    self.headers.push((name.into(), value.into()));
+    self
+  }
+}
+
+type Handler = dyn Fn(&Request) -> Response + Send + Sync;
+
+/// A minimal HTTP server on 127.0.0.1 answering every request with `handler`.
+/// It stops with the test's runtime.
+pub(crate) struct StandIn {
+  pub url: String,
+  requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl StandIn {
+  pub async fn start(handler: impl Fn(&Request) -> Response + Send + Sync + 'static) -> Self {
+    let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+    let url = format!("http://127.0.0.1:{}", listener.local_addr().unwrap().port());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let handler: Arc<Handler> = Arc::new(handler);
+
+    let received = requests.clone();
+    tokio::spawn(async move {
+      while let Ok((stream, _)) = listener.accept().await {
+        tokio::spawn(serve(stream, handler.clone(), received.clone()));
+      }
+    });
+    Self { url, requests }
+  }
+
+  /// How many requests were made for `path`.
+  pub fn hits(&self, path: &str) -> usize {
+    self
+      .requests
+      .lock()
+      .unwrap()
+      .iter()
+      .filter(|request| request.path == path)
+      .count()
+  }
+}
+
+async fn serve(mut stream: TcpStream, handler: Arc<Handler>, requests: Arc<Mutex<Vec<Request>>>) {
+  let Some(request) = read_request(&mut stream).await else {
+    return;
+  };
+  requests.lock().unwrap().push(request.clone());
+  let response = handler(&request);
+
+  let mut head = format!("HTTP/1.1 {} Stand-in\r\n", response.status);
+  for (name, value) in &response.headers {
+    head.push_str(&format!("{name}: {value}\r\n"));
+  }
+  head.push_str(&format!(
+    "Content-Length: {}\r\nConnection: close\r\n\r\n",
+    response.body.len()
+  ));
+  let _ = stream.write_all(head.as_bytes()).await;
+  let _ = stream.write_all(response.body.as_bytes()).await;
+  let _ = stream.shutdown().await;
+}
+
+async fn read_request(stream: &mut TcpStream) -> Option<Request> {
+  let mut data = Vec::new();
+  let mut buf = [0u8; 4096];
+  let head_end = loop {
+    if let Some(pos) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+      break pos + 4;
+    }
+    let n = stream.read(&mut buf).await.ok()?;
+    if n == 0 {
+      return None;
+    }
+    data.extend_from_slice(&buf[..n]);
+  };
+
+  let head = String::from_utf8_lossy(&data[..head_end]).into_owned();
+  let mut lines = head.lines();
+  let target = lines.next()?.split_whitespace().nth(1)?.to_string();
+  let content_length = lines
+    .filter_map(|line| line.split_once(':'))
+    .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+    .and_then(|(_, value)| value.trim().parse().ok())
+    .unwrap_or(0);
+  let mut body = data.split_off(head_end);
+  while body.len() < content_length {
+    let n = stream.read(&mut buf).await.ok()?;
+    if n == 0 {
+      return None;
+    }
+    body.extend_from_slice(&buf[..n]);
+  }
+
+  let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+  let parse = |bytes: &[u8]| url::form_urlencoded::parse(bytes).into_owned().collect();
+  Some(Request {
+    path: path.to_string(),
+    query: parse(query.as_bytes()),
+    form: parse(&body),
+  })
+}
+
+/// An RSA key generated for the test run, published in JWKs as `kid`.
+pub(crate) struct TestKey {
+  pub kid: &'static str,
+  encoding: EncodingKey,
+  jwk: Value,
+}
+
+impl TestKey {
+  fn generate(kid: &'static str) -> Self {
+    let key = RsaPrivateKey::new(&mut rand::thread_rng(), 2048).unwrap();
+    let der = key.to_pkcs1_der().unwrap();
+    Self {
+      kid,
+      encoding: EncodingKey::from_rsa_der(der.as_bytes()),
+      jwk: json!({
+        "kty": "RSA",
+        "use": "sig",
+        "alg": "RS256",
+        "kid": kid,
+        "n": URL_SAFE_NO_PAD.encode(key.n().to_bytes_be()),
+        "e": URL_SAFE_NO_PAD.encode(key.e().to_bytes_be()),
+      }),
+    }
+  }
+
+  #[cfg(feature = "mock")]
+  pub fn encoding_key(&self) -> EncodingKey {
+    self.encoding.clone()
+  }
+
+  /// Signs `claims` as an RS256 JWT naming this key.
+  pub fn sign(&self, claims: &Value) -> String {
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some(self.kid.into());
+    jsonwebtoken::encode(&header, claims, &self.encoding).unwrap()
+  }
+}
+
+/// Keys shared by every test, as generating them is slow in debug builds.
+pub(crate) fn keys() -> &'static [TestKey; 3] {
+  static KEYS: OnceLock<[TestKey; 3]> = OnceLock::new();
+  KEYS.get_or_init(|| {
+    [
+      TestKey::generate("key-1"),
+      TestKey::generate("key-2"),
+      TestKey::generate("key-3"),
+    ]
+  })
+}
+
+/// A JWKS document publishing `keys`.
+pub(crate) fn jwks(keys: &[&TestKey]) -> Value {
+  json!({ "keys": keys.iter().map(|key| key.jwk.clone()).collect::<Vec<_>>() })
+}
+
+/// ID token claims issued now by `iss` to `aud`, valid for an hour.
+pub(crate) fn claims(iss: &str, aud: &str) -> Value {
+  let now = unix_now();
+  json!({
+    "iss": iss,
+    "sub": "110248495921238986420",
+    "aud": aud,
+    "iat": now,
+    "exp": now + 3600,
+    "email": "ada@be-out.app",
+    "email_verified": true,
+    "hd": "be-out.app",
+    "name": "Ada Lovelace",
+  })
+}
+
+/// A fresh directory for a test's session store.
+pub(crate) fn temp_dir() -> PathBuf {
+  std::env::temp_dir().join(format!("google-auth-test-{}", random_token(9)))
+}